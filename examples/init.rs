@@ -4,16 +4,13 @@
 extern crate loggy;
 
 use log::LevelFilter;
-use loggy::Loggy;
 
 fn main() {
-    log::set_logger(&Loggy {
-        prefix: "example",
-        show_time: true,
-        show_thread: true,
-    })
-    .unwrap();
-    log::set_max_level(LevelFilter::Warn);
+    let _handle = builder!()
+        .show_time(true)
+        .show_thread(true)
+        .level(LevelFilter::Warn)
+        .init();
 
     note!(false, "This is a example message.");
 }
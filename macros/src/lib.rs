@@ -13,8 +13,15 @@ use syn::parse::Parse;
 use syn::parse::ParseStream;
 use syn::parse_macro_input;
 use syn::parse_quote;
+use syn::AttributeArgs;
+use syn::Error;
+use syn::Expr;
 use syn::ItemFn;
+use syn::Lit;
+use syn::LitInt;
 use syn::LitStr;
+use syn::Meta;
+use syn::NestedMeta;
 use syn::Result;
 use syn::Stmt;
 
@@ -53,3 +60,125 @@ pub fn scope(attributes: TokenStream, stream: TokenStream) -> TokenStream {
     let output = quote! { #input };
     output.into()
 }
+
+/// How to parse the arguments of a test.
+#[derive(Default)]
+struct TestOptions {
+    /// The expected log.
+    logs: Option<LitStr>,
+
+    /// The expected panic.
+    panics: Option<LitStr>,
+
+    /// The expected number of errors.
+    errors: Option<LitInt>,
+}
+
+impl TestOptions {
+    /// Whether no options were given.
+    const fn is_empty(&self) -> bool {
+        self.logs.is_none() && self.panics.is_none() && self.errors.is_none()
+    }
+
+    fn parse(arguments: AttributeArgs) -> Result<Self> {
+        let mut options = Self::default();
+        for argument in arguments {
+            let NestedMeta::Meta(Meta::NameValue(name_value)) = &argument else {
+                return Err(Error::new_spanned(argument, "expected `name = value`"));
+            };
+            match (
+                name_value
+                    .path
+                    .get_ident()
+                    .map(ToString::to_string)
+                    .as_deref(),
+                &name_value.lit,
+            ) {
+                (Some("logs"), Lit::Str(logs)) if options.logs.is_none() => {
+                    options.logs = Some(logs.clone())
+                }
+                (Some("panics"), Lit::Str(panics)) if options.panics.is_none() => {
+                    options.panics = Some(panics.clone())
+                }
+                (Some("errors"), Lit::Int(errors)) if options.errors.is_none() => {
+                    options.errors = Some(errors.clone())
+                }
+                _ => {
+                    return Err(Error::new_spanned(
+                        argument,
+                        "expected a single `logs = \"...\"`, `panics = \"...\"` or `errors = N`",
+                    ))
+                }
+            }
+        }
+        if let Some(errors) = &options.errors {
+            if options.panics.is_some() {
+                return Err(Error::new_spanned(
+                    errors,
+                    "`errors` can't be combined with `panics`",
+                ));
+            }
+            if options.logs.is_none() {
+                return Err(Error::new_spanned(errors, "`errors` requires `logs`"));
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Mark a function as a test which logs messages.
+///
+/// To use this, prefix the test with `#[loggy::test(...)]` instead of `#[test]`. The test body is executed in a scope
+/// named after the test function, so all log messages generated by it are prefixed by the test name, and:
+///
+/// * `#[loggy::test(logs = "...")]` asserts the test generates the expected log (as in `assert_logs`).
+///
+/// * `#[loggy::test(panics = "...")]` asserts the test panics with the expected message (as in `assert_panics`). This
+///   may be combined with `logs` (as in `assert_logs_panics`).
+///
+/// * `#[loggy::test(logs = "...", errors = N)]` asserts the test generates the expected log, which contains `N`
+///   errors, so the scope fails (as in `assert_errors_count`).
+///
+/// Without any options, this is identical to `#[test]`, and the test item is passed to it as-is (without parsing it).
+/// This is important since `#[macro_use] extern crate loggy;` causes `#[test]` to refer to this attribute.
+///
+/// # Panics
+///
+/// If the test does not generate the expected log, panic or errors.
+#[proc_macro_attribute]
+pub fn test(attributes: TokenStream, stream: TokenStream) -> TokenStream {
+    let options = if attributes.is_empty() {
+        TestOptions::default()
+    } else {
+        match TestOptions::parse(parse_macro_input!(attributes as AttributeArgs)) {
+            Ok(options) => options,
+            Err(error) => return error.to_compile_error().into(),
+        }
+    };
+    if options.is_empty() {
+        let mut output: TokenStream = "#[::core::prelude::v1::test]".parse().unwrap();
+        output.extend(stream);
+        return output;
+    }
+    let mut input = parse_macro_input!(stream as ItemFn);
+    let name = input.sig.ident.to_string();
+    let block = &input.block;
+    let scoped: Expr = parse_quote! { loggy::Scope::with(#name, || #block) };
+    let body: Expr = match (options.logs, options.panics, options.errors) {
+        (Some(logs), None, Some(errors)) => {
+            parse_quote! { loggy::assert_errors_count(#name, #errors, #logs, || #block) }
+        }
+        (Some(logs), Some(panics), None) => {
+            parse_quote! { loggy::assert_logs_panics(#logs, #panics, || #scoped) }
+        }
+        (None, Some(panics), None) => parse_quote! { loggy::assert_panics(#panics, || #scoped) },
+        (Some(logs), None, None) => parse_quote! { loggy::assert_logs(#logs, || #scoped) },
+        _ => unreachable!(),
+    };
+    input.block.stmts = vec![Stmt::Expr(body)];
+    let output = quote! {
+        #[::core::prelude::v1::test]
+        #input
+    };
+    output.into()
+}
@@ -0,0 +1,529 @@
+// Copyright (C) 2017-2021 Oren Ben-Kiki. See the LICENSE.txt file at the top-level directory of this distribution and
+// at http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not
+// be copied, modified, or distributed except according to those terms.
+
+//! Matching actual text against expected text which may contain placeholders.
+
+use regex::Regex;
+use std::fmt::Write;
+use std::sync::LazyLock;
+
+/// The number of unchanged lines to show around each change in a diff.
+const DIFF_CONTEXT: usize = 3;
+
+/// The regular expression matching a time stamp in a log message.
+const TIME_REGEX: &str = r"\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}\.\d{3}";
+
+/// The regular expression matching a thread index in a log message.
+const THREAD_REGEX: &str = r"\d+";
+
+/// The regular expression matching a (possibly negative, possibly fractional) number.
+const NUMBER_REGEX: &str = r"-?\d+(?:\.\d+)?";
+
+/// The regular expression matching any text on the line.
+const ANY_REGEX: &str = r".*";
+
+/// How to match a single line of the expected text.
+struct Line {
+    /// The line as given in the expected text.
+    text: String,
+
+    /// The regular expression to match if the line contains placeholders.
+    pattern: Option<Regex>,
+}
+
+impl Line {
+    /// A line that must be identical to the text.
+    fn literal(text: &str) -> Self {
+        Self {
+            text: text.to_owned(),
+            pattern: None,
+        }
+    }
+
+    /// Parse a single line of the expected text.
+    fn parse(text: &str) -> Self {
+        let mut pattern = String::from("^");
+        let mut literal = String::new();
+        let mut has_placeholders = false;
+        let mut rest = text;
+
+        while let Some(start) = rest.find('{') {
+            literal.push_str(&rest[..start]);
+            pattern.push_str(&regex::escape(&rest[..start]));
+            rest = &rest[start..];
+
+            let placeholder = if rest.starts_with("{{") {
+                literal.push('{');
+                pattern.push_str(r"\{");
+                rest = &rest[2..];
+                continue;
+            } else if let Some(tail) = rest.strip_prefix("{*}") {
+                rest = tail;
+                ANY_REGEX
+            } else if let Some(tail) = rest.strip_prefix("{time}") {
+                rest = tail;
+                TIME_REGEX
+            } else if let Some(tail) = rest.strip_prefix("{thread}") {
+                rest = tail;
+                THREAD_REGEX
+            } else if let Some(tail) = rest.strip_prefix("{number}") {
+                rest = tail;
+                NUMBER_REGEX
+            } else if let Some((regex, tail)) = rest
+                .strip_prefix("{/")
+                .and_then(|tail| tail.split_once("/}"))
+            {
+                rest = tail;
+                regex
+            } else {
+                literal.push('{');
+                pattern.push_str(r"\{");
+                rest = &rest[1..];
+                continue;
+            };
+
+            has_placeholders = true;
+            pattern.push_str("(?:");
+            pattern.push_str(placeholder);
+            pattern.push(')');
+        }
+
+        if !has_placeholders {
+            literal.push_str(rest);
+            return Self::literal(&literal);
+        }
+
+        pattern.push_str(&regex::escape(rest));
+        pattern.push('$');
+        match Regex::new(&pattern) {
+            Ok(regex) => Self {
+                text: text.to_owned(),
+                pattern: Some(regex),
+            },
+            Err(error) => std::panic!("invalid pattern in expected line: {text}\n{error}"), // NOT TESTED
+        }
+    }
+
+    /// Whether an actual line matches this expected line.
+    fn is_match(&self, actual: &str) -> bool {
+        self.pattern
+            .as_ref()
+            .map_or_else(|| self.text == actual, |pattern| pattern.is_match(actual))
+    }
+}
+
+/// A single step in the difference between the expected and actual lines.
+#[derive(Clone, Copy)]
+enum Edit {
+    /// The expected line (first index) matches the actual line (second index).
+    Same(usize, usize),
+
+    /// The expected line (index) is missing from the actual text.
+    Removed(usize),
+
+    /// The actual line (index) is not in the expected text.
+    Added(usize),
+}
+
+/// The line starting a block of messages that may appear in any order.
+const UNORDERED_START: &str = "{unordered}";
+
+/// The line ending a block of messages that may appear in any order.
+const UNORDERED_END: &str = "{/unordered}";
+
+/// The regular expression matching an expected continuation line of a multi-line log message.
+///
+/// This is a line with a lower-case level (e.g., `[info]`) right after the prefix, thread and time (if any).
+static CONTINUATION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(&format!(
+        r"^\S*: (?:(?:\{{time\}}|{TIME_REGEX}) )?\[(?:error|warn|info|debug|trace)\](?: |$)"
+    ))
+    .unwrap()
+});
+
+/// A part of the expected text.
+enum Item {
+    /// A single line which must match in order.
+    Line(Line),
+
+    /// Multi-line messages which may appear in any order (each kept contiguous).
+    Unordered(Vec<Vec<Line>>),
+}
+
+/// Expected text, which may contain placeholders for nondeterministic parts of the actual text.
+///
+/// The supported placeholders are:
+///
+/// * `{*}` matches any text on the line.
+/// * `{time}` matches a time stamp, as emitted when `show_time` is set.
+/// * `{thread}` matches a thread index, as emitted when `show_thread` is set.
+/// * `{number}` matches an integer or a decimal number (possibly negative).
+/// * `{/regex/}` matches the regular expression (which must not span multiple lines).
+/// * `{{` matches a literal `{`.
+///
+/// Any other `{` is matched literally.
+///
+/// In addition, the lines between a `{unordered}` line and a `{/unordered}` line are a block of log messages that may
+/// appear in any order. Each message starts with a line with an upper-case level (e.g., `[INFO]`) and includes the
+/// following continuation lines with a lower-case level (e.g., `[info]`). Each message must appear contiguously. The
+/// actual messages are matched against the first (unused) expected message they match, so more specific expected
+/// messages should be listed before more general ones.
+pub struct Expected {
+    /// How to match the lines of the actual text.
+    items: Vec<Item>,
+}
+
+impl Expected {
+    /// Expected text that must be identical to the actual text (without any placeholders).
+    pub fn literal(text: &str) -> Self {
+        Self {
+            items: text
+                .split('\n')
+                .map(|line| Item::Line(Line::literal(line)))
+                .collect(),
+        }
+    }
+
+    /// Parse the (already unindented) expected text.
+    pub fn parse(text: &str) -> Self {
+        let mut items = vec![];
+        let mut unordered: Option<Vec<Vec<Line>>> = None;
+        for line in text.split('\n') {
+            match (line, unordered.as_mut()) {
+                (UNORDERED_START, None) => unordered = Some(vec![]),
+                (UNORDERED_END, Some(_)) => items.push(Item::Unordered(unordered.take().unwrap())),
+                (_, Some(messages)) => {
+                    let is_continuation = CONTINUATION_REGEX.is_match(line);
+                    let line = Line::parse(line);
+                    match messages.last_mut() {
+                        Some(message) if is_continuation => message.push(line),
+                        _ => messages.push(vec![line]),
+                    }
+                }
+                (_, None) => items.push(Item::Line(Line::parse(line))),
+            }
+        }
+        assert!(
+            unordered.is_none(),
+            "missing {UNORDERED_END} line in expected text"
+        );
+        Self { items }
+    }
+
+    /// Whether the last expected line is empty (that is, the expected text is empty or ends with a newline).
+    fn has_empty_last_line(&self) -> bool {
+        matches!(self.items.last(), Some(Item::Line(line)) if line.pattern.is_none() && line.text.is_empty())
+    }
+
+    /// Whether the actual text matches the expected text.
+    pub fn is_match(&self, actual: &str) -> bool {
+        let actual_lines: Vec<&str> = actual.split('\n').collect();
+        match_items(&self.items, &actual_lines)
+    }
+
+    /// Arrange the expected lines in the order best matching the actual lines.
+    ///
+    /// The messages of each unordered block are placed in the order they are found in a single pass over the actual
+    /// lines (starting where the previous items matched), followed by the messages which were not found.
+    fn arranged_lines(&self, actual: &[&str]) -> Vec<&Line> {
+        let mut lines = vec![];
+        let mut position = 0;
+        for item in &self.items {
+            match item {
+                Item::Line(line) => {
+                    if position < actual.len() && line.is_match(actual[position]) {
+                        position += 1;
+                    }
+                    lines.push(line);
+                }
+                Item::Unordered(messages) => {
+                    let mut used = vec![false; messages.len()];
+                    let mut start = position;
+                    while start < actual.len() && used.contains(&false) {
+                        match match_message(messages, &mut used, &actual[start..]) {
+                            Some(index) => {
+                                lines.extend(&messages[index]);
+                                start += messages[index].len();
+                                position = start;
+                            }
+                            None => start += 1,
+                        }
+                    }
+                    for (message, is_used) in messages.iter().zip(used) {
+                        if !is_used {
+                            lines.extend(message);
+                        }
+                    }
+                }
+            }
+        }
+        lines
+    }
+
+    /// Describe the differences between the expected and the actual text as a unified diff.
+    ///
+    /// Each line is prefixed by its line number in the expected and/or actual text. If `color` is set, removed lines
+    /// are shown in red, added lines in green, and hunk headers in cyan. The empty line following a final newline is
+    /// not shown, but if only one of the texts ends with a newline, this is noted at the end of the diff.
+    pub fn diff(&self, actual: &str, color: bool) -> String {
+        let mut actual_lines: Vec<&str> = actual.split('\n').collect();
+        let actual_newline = actual.ends_with('\n');
+        if actual_lines.last() == Some(&"") {
+            actual_lines.pop();
+        }
+        let mut lines = self.arranged_lines(&actual_lines);
+        let expected_newline = self.items.len() > 1 && self.has_empty_last_line();
+        if self.has_empty_last_line() {
+            lines.pop();
+        }
+        let edits = edits(&lines, &actual_lines);
+        let width = lines.len().max(actual_lines.len()).to_string().len();
+        let (red, green, cyan, reset) = if color {
+            ("\x1b[31m", "\x1b[32m", "\x1b[36m", "\x1b[0m")
+        } else {
+            ("", "", "", "")
+        };
+
+        let mut diff = format!("{red}--- expected{reset}\n{green}+++ actual{reset}\n");
+        for (start, end) in hunks(&edits) {
+            let hunk = &edits[start..end];
+            let (expected_start, actual_start) = edits[..start]
+                .iter()
+                .fold((0, 0), |(expected, actual), edit| {
+                    advance(*edit, expected, actual)
+                });
+            let (expected_end, actual_end) = hunk.iter().fold(
+                (expected_start, actual_start),
+                |(expected, actual), edit| advance(*edit, expected, actual),
+            );
+            writeln!(
+                diff,
+                "{cyan}@@ -{},{} +{},{} @@{reset}",
+                expected_start + 1,
+                expected_end - expected_start,
+                actual_start + 1,
+                actual_end - actual_start
+            )
+            .unwrap();
+
+            for edit in hunk {
+                match *edit {
+                    Edit::Same(expected, actual) => writeln!(
+                        diff,
+                        " {:>width$} {:>width$} {}",
+                        expected + 1,
+                        actual + 1,
+                        actual_lines[actual]
+                    ),
+                    Edit::Removed(expected) => writeln!(
+                        diff,
+                        "{red}-{:>width$} {:>width$} {}{reset}",
+                        expected + 1,
+                        "",
+                        lines[expected].text
+                    ),
+                    Edit::Added(actual) => writeln!(
+                        diff,
+                        "{green}+{:>width$} {:>width$} {}{reset}",
+                        "",
+                        actual + 1,
+                        actual_lines[actual]
+                    ),
+                }
+                .unwrap();
+            }
+        }
+
+        if expected_newline != actual_newline {
+            let (text, count) = if expected_newline {
+                ("actual", actual_lines.len())
+            } else {
+                ("expected", lines.len())
+            };
+            if count > 0 {
+                writeln!(diff, "\\ No newline at end of {text}").unwrap();
+            }
+        }
+        diff
+    }
+}
+
+/// Compute the (minimal) edits converting the expected lines to the actual lines.
+fn edits(lines: &[&Line], actual: &[&str]) -> Vec<Edit> {
+    let is_match =
+        |expected: usize, actual_index: usize| lines[expected].is_match(actual[actual_index]);
+    let expected_count = lines.len();
+    let actual_count = actual.len();
+
+    let mut prefix = 0;
+    while prefix < expected_count.min(actual_count) && is_match(prefix, prefix) {
+        prefix += 1;
+    }
+
+    let mut suffix = 0;
+    while suffix < (expected_count - prefix).min(actual_count - prefix)
+        && is_match(expected_count - 1 - suffix, actual_count - 1 - suffix)
+    {
+        suffix += 1;
+    }
+
+    // Longest common subsequence of the suffixes of the middle parts.
+    let rows = expected_count - prefix - suffix;
+    let columns = actual_count - prefix - suffix;
+    let mut common = vec![vec![0_usize; columns + 1]; rows + 1];
+    for row in (0..rows).rev() {
+        for column in (0..columns).rev() {
+            common[row][column] = if is_match(prefix + row, prefix + column) {
+                common[row + 1][column + 1] + 1
+            } else {
+                common[row + 1][column].max(common[row][column + 1])
+            };
+        }
+    }
+
+    let mut edits: Vec<Edit> = (0..prefix).map(|index| Edit::Same(index, index)).collect();
+    let (mut row, mut column) = (0, 0);
+    while row < rows || column < columns {
+        if row < rows && column < columns && is_match(prefix + row, prefix + column) {
+            edits.push(Edit::Same(prefix + row, prefix + column));
+            row += 1;
+            column += 1;
+        } else if row < rows
+            && (column == columns || common[row + 1][column] >= common[row][column + 1])
+        {
+            edits.push(Edit::Removed(prefix + row));
+            row += 1;
+        } else {
+            edits.push(Edit::Added(prefix + column));
+            column += 1;
+        }
+    }
+    edits.extend(
+        (0..suffix).map(|index| Edit::Same(prefix + rows + index, prefix + columns + index)),
+    );
+    edits
+}
+
+/// Whether the actual lines match the expected items.
+fn match_items(items: &[Item], actual: &[&str]) -> bool {
+    match items.split_first() {
+        None => actual.is_empty(),
+        Some((Item::Line(line), rest)) => actual
+            .split_first()
+            .is_some_and(|(first, tail)| line.is_match(first) && match_items(rest, tail)),
+        Some((Item::Unordered(messages), rest)) => {
+            let mut used = vec![false; messages.len()];
+            let mut actual = actual;
+            while used.contains(&false) {
+                match match_message(messages, &mut used, actual) {
+                    Some(index) => actual = &actual[messages[index].len()..],
+                    None => return false,
+                }
+            }
+            match_items(rest, actual)
+        }
+    }
+}
+
+/// The index of the first unused message of an unordered block which the actual lines start with (if any), which is
+/// then marked as used.
+fn match_message(messages: &[Vec<Line>], used: &mut [bool], actual: &[&str]) -> Option<usize> {
+    let index = messages
+        .iter()
+        .zip(used.iter())
+        .position(|(message, is_used)| !is_used && match_lines(message, actual))?;
+    used[index] = true;
+    Some(index)
+}
+
+/// Whether the actual lines start with the expected lines.
+fn match_lines(lines: &[Line], actual: &[&str]) -> bool {
+    lines.len() <= actual.len()
+        && lines
+            .iter()
+            .zip(actual)
+            .all(|(line, actual)| line.is_match(actual))
+}
+
+/// Advance the expected and actual line counters past an edit.
+const fn advance(edit: Edit, expected: usize, actual: usize) -> (usize, usize) {
+    match edit {
+        Edit::Same(_, _) => (expected + 1, actual + 1),
+        Edit::Removed(_) => (expected + 1, actual),
+        Edit::Added(_) => (expected, actual + 1),
+    }
+}
+
+/// Group the edits into hunks (ranges of edits) containing the changes surrounded by some unchanged context lines.
+fn hunks(edits: &[Edit]) -> Vec<(usize, usize)> {
+    let mut hunks: Vec<(usize, usize)> = vec![];
+    for (index, edit) in edits.iter().enumerate() {
+        if matches!(edit, Edit::Same(_, _)) {
+            continue;
+        }
+        let start = index.saturating_sub(DIFF_CONTEXT);
+        let end = (index + 1 + DIFF_CONTEXT).min(edits.len());
+        match hunks.last_mut() {
+            Some(last) if last.1 >= start => last.1 = end,
+            _ => hunks.push((start, end)),
+        }
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Expected;
+
+    #[test]
+    fn diff_should_show_hunks_with_line_numbers() {
+        let expected = Expected::parse("1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n11\n12\n");
+        assert_eq!(
+            expected.diff("1\ntwo\n3\n4\n5\n6\n7\n8\n9\n10\n12\n", false),
+            concat!(
+                "--- expected\n",
+                "+++ actual\n",
+                "@@ -1,5 +1,5 @@\n",
+                "  1  1 1\n",
+                "- 2    2\n",
+                "+    2 two\n",
+                "  3  3 3\n",
+                "  4  4 4\n",
+                "  5  5 5\n",
+                "@@ -8,5 +8,4 @@\n",
+                "  8  8 8\n",
+                "  9  9 9\n",
+                " 10 10 10\n",
+                "-11    11\n",
+                " 12 11 12\n",
+            )
+        );
+    }
+
+    #[test]
+    fn diff_should_note_a_missing_final_newline() {
+        assert_eq!(
+            Expected::parse("1\n2").diff("1\n2\n", false),
+            concat!(
+                "--- expected\n",
+                "+++ actual\n",
+                "\\ No newline at end of expected\n",
+            )
+        );
+        assert_eq!(
+            Expected::parse("1\n2\n").diff("1\n3", false),
+            concat!(
+                "--- expected\n",
+                "+++ actual\n",
+                "@@ -1,2 +1,2 @@\n",
+                " 1 1 1\n",
+                "-2   2\n",
+                "+  2 3\n",
+                "\\ No newline at end of actual\n",
+            )
+        );
+    }
+}
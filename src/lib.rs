@@ -13,19 +13,36 @@
 #![deny(clippy::nursery)]
 #![deny(clippy::cargo)]
 
-pub use loggy_macros::scope;
-
-use chrono::Utc;
-use lazy_static::lazy_static;
-use log::{logger, set_logger, set_max_level, Level, LevelFilter, Log, Metadata, Record};
-use parking_lot::Mutex;
-use std::cell::Cell;
-use std::fmt::Write;
-use std::io::{stderr, Write as IoWrite};
-use std::marker::PhantomData;
+pub use loggy_macros::{scope, test};
+
+mod expected;
+#[cfg(unix)]
+mod redirect;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use expected::Expected;
+use log::{
+    set_boxed_logger, set_logger, set_max_level, Level, LevelFilter, Log, Metadata, Record,
+    SetLoggerError,
+};
+use parking_lot::{Mutex, RwLock};
+use regex::Regex;
+use std::any::Any;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::error::Error;
+use std::fmt::{Display, Formatter, Write};
+use std::fs::{File, OpenOptions};
+use std::future::Future;
+use std::io::{stderr, IsTerminal, Write as IoWrite};
 use std::panic::{catch_unwind, set_hook, take_hook, AssertUnwindSafe};
-use std::sync::atomic::AtomicBool;
-use std::thread::panicking;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock, Once};
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{panicking, JoinHandle, ThreadId};
 use unindent::unindent;
 
 /// The current crate version: 0.5.3
@@ -54,8 +71,15 @@ macro_rules! log {
                 #[allow(unused_mut)]
                 let mut string = format!($format);
                 $(
-                    let mut indent = "  ".to_owned();
-                    log!( @collect string , indent , $( $tail )* );
+                    let _fields = if loggy::FieldsGuard::is_capturing() {
+                        let mut fields = Vec::new();
+                        log!( @collect fields , $( $tail )* );
+                        Some(loggy::FieldsGuard::new(&mut string, fields))
+                    } else {
+                        let mut indent = "  ".to_owned();
+                        log!( @render string , indent , $( $tail )* );
+                        None
+                    };
                 )?
                 log::log!( $level , "{}" , string );
             }
@@ -68,43 +92,81 @@ macro_rules! log {
                 #[allow(unused_mut)]
                 let mut string = format!($format $( , $value )* );
                 $(
-                    let mut indent = "  ".to_owned();
-                    log!( @collect string , indent , $( $tail )* );
+                    let _fields = if loggy::FieldsGuard::is_capturing() {
+                        let mut fields = Vec::new();
+                        log!( @collect fields , $( $tail )* );
+                        Some(loggy::FieldsGuard::new(&mut string, fields))
+                    } else {
+                        let mut indent = "  ".to_owned();
+                        log!( @render string , indent , $( $tail )* );
+                        None
+                    };
                 )?
                 log::log!( $level , "{}" , string );
             }
         }
     };
 
-    ( @collect $string:ident , $indent:ident , $name:ident $( , )? ) => {
+    ( @render $string:ident , $indent:ident , $name:ident $( , )? ) => {
         $string.push_str(format!("\n{}{}: {}", $indent, stringify!($name), $name).as_str());
     };
 
-    ( @collect $string:ident , $indent:ident , $name:ident , $( $tail:tt )* ) => {
-        log!( @collect $string , $indent , $name );
-        log!( @collect $string , $indent , $( $tail )* );
+    ( @render $string:ident , $indent:ident , $name:ident , $( $tail:tt )* ) => {
+        log!( @render $string , $indent , $name );
+        log!( @render $string , $indent , $( $tail )* );
     };
 
-    ( @collect $string:ident, $indent:ident , $name:ident => $value:expr $( , )? ) => {
+    ( @render $string:ident, $indent:ident , $name:ident => $value:expr $( , )? ) => {
         $string.push_str(format!("\n{}{}: {}", $indent, stringify!($name), $value).as_str());
     };
 
-    ( @collect $string:ident, $indent:ident , $name:ident => $value:expr , $( $tail:tt )* ) => {
-        log!( @collect $string , $indent , $name => $value );
-        log!( @collect $string , $indent , $( $tail )* );
+    ( @render $string:ident, $indent:ident , $name:ident => $value:expr , $( $tail:tt )* ) => {
+        log!( @render $string , $indent , $name => $value );
+        log!( @render $string , $indent , $( $tail )* );
     };
 
-    ( @collect $string:ident , $indent:ident, $name:ident { $( $nest:tt )* } $( , )? ) => {
+    ( @render $string:ident , $indent:ident, $name:ident { $( $nest:tt )* } $( , )? ) => {
         $string.push_str(format!("\n{}{}:", $indent, stringify!($name)).as_str());
         $indent.push_str("  ");
-        log!( @collect $string , $indent , $( $nest )* );
+        log!( @render $string , $indent , $( $nest )* );
         $indent.pop();
         $indent.pop();
     };
 
-    ( @collect $string:ident , $indent:ident, $name:ident { $( $nest:tt )* } , $( $tail:tt )* ) => {
-        log!( @collect $string , $indent , $name { $( $nest )* } );
-        log!( @collect $string , $indent , $( $tail )* );
+    ( @render $string:ident , $indent:ident, $name:ident { $( $nest:tt )* } , $( $tail:tt )* ) => {
+        log!( @render $string , $indent , $name { $( $nest )* } );
+        log!( @render $string , $indent , $( $tail )* );
+    };
+
+    ( @collect $fields:ident , $name:ident $( , )? ) => {
+        $fields.push(loggy::Field::value(stringify!($name), format!("{}", $name)));
+    };
+
+    ( @collect $fields:ident , $name:ident , $( $tail:tt )* ) => {
+        log!( @collect $fields , $name );
+        log!( @collect $fields , $( $tail )* );
+    };
+
+    ( @collect $fields:ident , $name:ident => $value:expr $( , )? ) => {
+        $fields.push(loggy::Field::value(stringify!($name), format!("{}", $value)));
+    };
+
+    ( @collect $fields:ident , $name:ident => $value:expr , $( $tail:tt )* ) => {
+        log!( @collect $fields , $name => $value );
+        log!( @collect $fields , $( $tail )* );
+    };
+
+    ( @collect $fields:ident , $name:ident { $( $nest:tt )* } $( , )? ) => {
+        {
+            let mut nested = Vec::new();
+            log!( @collect nested , $( $nest )* );
+            $fields.push(loggy::Field::nested(stringify!($name), nested));
+        }
+    };
+
+    ( @collect $fields:ident , $name:ident { $( $nest:tt )* } , $( $tail:tt )* ) => {
+        log!( @collect $fields , $name { $( $nest )* } );
+        log!( @collect $fields , $( $tail )* );
     };
 }
 
@@ -170,8 +232,9 @@ macro_rules! info { ( $( $arg:tt )* ) => { loggy::log!( log::Level::Info , $( $a
 /// This is identical to invoking `log!(log::Level::Debug, ...)`.
 ///
 /// Debug messages are special. The are always emitted in debug builds, regardless of the requested
-/// log level. They are never captured by tests, and instead are always sent to the standard error.
-/// The idea being that debug messages are used for, well, debugging.
+/// log level. They are not captured by tests (unless requested using [`CaptureHandle::set_capture_debug`]), and instead
+/// are always sent to the log output (by default, the standard error). The idea being that debug messages are used
+/// for, well, debugging.
 #[macro_export]
 macro_rules! debug { ( $( $arg:tt )* ) => { loggy::log!( log::Level::Debug , $( $arg )* ) } }
 
@@ -225,121 +288,615 @@ macro_rules! is_an_error {
 // END FLAKY TESTED
 
 /// A named scope for log messages and [`error`]s.
-#[derive(Clone, Copy)]
+#[derive(Clone)]
 struct NamedScope {
     /// The scope name (to replace the module name in the messages).
-    name: &'static str,
+    name: Cow<'static, str>,
 
     /// An index (to follow the scope name, if given).
     index: Option<isize>,
 
-    /// The number of errors we've seen in the scope.
-    errors: usize,
+    /// The messages of the errors we've seen in the scope.
+    ///
+    /// This is shared with the copies of the scope attached to futures (see [`with_log_context`]).
+    errors: Arc<Mutex<Vec<String>>>,
 }
 
 thread_local! { // FLAKY TESTED
-    static NAMED_SCOPE: Cell<Option<NamedScope>> = Cell::new(None);
+    /// The (nested) named scopes of the current thread; the innermost one is the last.
+    static NAMED_SCOPES: RefCell<Vec<NamedScope>> = const { RefCell::new(Vec::new()) };
+}
+
+impl NamedScope {
+    /// Append the scope name (and index, if any) to a message.
+    fn append_to(&self, message: &mut String) {
+        message.push_str(&self.name);
+        if let Some(index) = self.index {
+            write!(message, "@{index}").unwrap();
+        }
+    }
+
+    /// Append the name of the innermost of some nested scopes (or their path, if showing it) to a message.
+    fn append_path_to(named_scopes: &[Self], scope_path: Option<ScopePath>, message: &mut String) {
+        let first = scope_path.map_or(named_scopes.len() - 1, |scope_path| {
+            scope_path
+                .depth
+                .map_or(0, |depth| named_scopes.len().saturating_sub(depth.max(1)))
+        });
+        for (index, named_scope) in named_scopes[first..].iter().enumerate() {
+            if index > 0 {
+                message.push_str(scope_path.unwrap().separator);
+            }
+            named_scope.append_to(message);
+        }
+    }
+}
+
+/// How to show the path of nested scopes in log messages (see [`set_scope_path`]).
+#[derive(Clone, Copy, Debug)]
+pub struct ScopePath {
+    /// The separator between the names of the nested scopes.
+    pub separator: &'static str,
+
+    /// The maximal number of (innermost) scopes to show, if limited.
+    pub depth: Option<usize>,
+}
+
+impl Default for ScopePath {
+    /// Show all the nested scopes, separated by `/`.
+    fn default() -> Self {
+        Self {
+            separator: "/",
+            depth: None,
+        }
+    }
+}
+
+/// Settings which affect all log messages, and which may be overridden for a log capture (see [`CaptureHandle`]).
+///
+/// Each setting is `None` if it has the default value (in a capture, if it uses the global setting).
+#[derive(Clone, Default)]
+struct Settings {
+    /// The clock used for the time stamps (see [`set_clock`]).
+    clock: Option<Arc<dyn Clock>>,
+
+    /// How to show the path of nested scopes (see [`set_scope_path`]).
+    scope_path: Option<ScopePath>,
+
+    /// The default failure policy of scopes (see [`set_failure_policy`]).
+    failure_policy: Option<FailurePolicy>,
+
+    /// The per-module and per-scope maximal levels (see [`set_directives`]).
+    directives: Option<Arc<Directives>>,
+}
+
+/// The global settings.
+static SETTINGS: RwLock<Settings> = parking_lot::const_rwlock(Settings {
+    clock: None,
+    scope_path: None,
+    failure_policy: None,
+    directives: None,
+});
+
+impl Settings {
+    /// The settings in effect for the current thread.
+    ///
+    /// This is resolved once per log message, combining the overrides of the capture the thread contributes to (if
+    /// any) with the global settings.
+    fn current() -> Self {
+        let global = SETTINGS.read().clone();
+        CAPTURES.with(|captures| match captures.borrow().last() {
+            None => global,
+            Some(capture) => {
+                let overrides = capture.state.settings.read();
+                Self {
+                    clock: overrides.clock.clone().or(global.clock),
+                    scope_path: overrides.scope_path.or(global.scope_path),
+                    failure_policy: overrides.failure_policy.or(global.failure_policy),
+                    directives: overrides.directives.clone().or(global.directives),
+                }
+            }
+        })
+    }
+
+    /// The current time according to the clock.
+    fn now(&self) -> DateTime<Utc> {
+        self.clock
+            .as_ref()
+            .map_or_else(Utc::now, |clock| clock.now())
+    }
+
+    /// Whether a log message is enabled.
+    ///
+    /// Error messages are always enabled, and so are debug messages.
+    fn is_enabled(&self, metadata: &Metadata<'_>) -> bool {
+        let level = metadata.level();
+        level == Level::Error || level == Level::Debug || level <= self.max_level(metadata.target())
+    }
+
+    /// The maximal level of the messages logged by some module in the current thread.
+    fn max_level(&self, module_path: &str) -> LevelFilter {
+        self.directives
+            .as_ref()
+            .and_then(|directives| directives.level(module_path).or(directives.default))
+            .unwrap_or_else(log::max_level)
+    }
+}
+
+/// Set how to show the path of nested scopes in log messages.
+///
+/// By default (`None`), log messages only show the name of the innermost scope, and errors are only counted by the
+/// innermost scope. When showing scope paths, log messages show the names of all the nested scopes (e.g.,
+/// `build/compile@3/link:`), possibly limited to some depth (in which case only the innermost scopes are shown), and
+/// errors are counted by all the nested scopes. Therefore, even if the innermost scope panics due to errors, and this
+/// panic is caught, the enclosing scopes will also fail.
+pub fn set_scope_path(scope_path: Option<ScopePath>) {
+    SETTINGS.write().scope_path = scope_path;
+}
+
+/// Access the innermost named scope of the current thread (if any).
+fn with_current_scope<T, Code: FnOnce(Option<&NamedScope>) -> T>(code: Code) -> T {
+    NAMED_SCOPES.with(|named_scopes| code(named_scopes.borrow().last()))
 }
 
 /// How many errors were seen so far in the current [`Scope`] (or 0 if outside one).
 #[must_use] // FLAKY TESTED
 pub fn scope_errors() -> usize {
-    NAMED_SCOPE.with(|named_scope| {
-        named_scope
-            .get() // FLAKY TESTED
-            .map_or(0, |named_scope| named_scope.errors)
+    with_current_scope(|named_scope| {
+        named_scope.map_or(0, |named_scope| named_scope.errors.lock().len())
+    })
+}
+
+/// What to do when errors are reported in a [`Scope`], or outside any scope.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Panic when the scope ends (or immediately, for errors outside any scope).
+    #[default]
+    Panic,
+
+    /// Report the failure to the log output (see [`LoggyBuilder::output`] and [`set_log_file`]) and exit the process
+    /// with some status.
+    Exit(i32),
+
+    /// Just count the errors and continue (they are still available using [`scope_errors`]).
+    Continue,
+}
+
+/// Set the default failure policy for scopes (and for errors outside any scope).
+///
+/// This applies to all scopes whose policy was not set using [`Scope::set_failure_policy`]. Regardless of the policy,
+/// [`Scope::try_with`] and [`Scope::finish`] always return the failure as an error.
+pub fn set_failure_policy(failure_policy: FailurePolicy) {
+    SETTINGS.write().failure_policy = Some(failure_policy);
+}
+
+/// Per-module and per-scope maximal levels of log messages (see [`set_directives`]).
+///
+/// This is parsed from a comma-separated list of directives, similar to `RUST_LOG`. For example,
+/// `info,parser=trace,scope:link=warn` means that messages logged inside a scope named `link` are emitted up to the
+/// `Warn` level, other messages logged by the `parser` module (or its sub-modules) are emitted up to the `Trace` level,
+/// and all other messages are emitted up to the `Info` level. Each directive is one of:
+///
+/// * `level` sets the default maximal level (`off`, `error`, `warn`, `info`, `debug` or `trace`).
+///
+/// * `module=level` (or just `module`, meaning `trace`) sets the maximal level of the messages logged by a module
+///   path, which must be identifiers separated by `::` (the longest matching module path wins).
+///
+/// * `scope:name=level` (or just `scope:name`, meaning `trace`) sets the maximal level of the messages logged inside
+///   any scope with this name (the innermost matching scope wins, and takes precedence over the module paths).
+///
+/// As always, error messages are always emitted, and so are debug messages in debug builds.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Directives {
+    /// The default maximal level, if specified.
+    pub default: Option<LevelFilter>,
+
+    /// The maximal level for each module path.
+    pub modules: Vec<(String, LevelFilter)>,
+
+    /// The maximal level for each scope name.
+    pub scopes: Vec<(String, LevelFilter)>,
+}
+
+/// An invalid [`Directives`] string.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidDirective {
+    /// The invalid directive.
+    pub directive: String,
+}
+
+impl Display for InvalidDirective {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(formatter, "invalid log level directive: {}", self.directive)
+    }
+}
+
+impl Error for InvalidDirective {}
+
+impl FromStr for Directives {
+    type Err = InvalidDirective;
+
+    fn from_str(string: &str) -> Result<Self, Self::Err> {
+        let mut directives = Self::default();
+        for directive in string
+            .split(',')
+            .map(str::trim)
+            .filter(|directive| !directive.is_empty())
+        {
+            let invalid = || InvalidDirective {
+                directive: directive.to_owned(),
+            };
+            let (name, level) = match directive.split_once('=') {
+                Some((name, level)) => (name.trim(), level.trim().parse().map_err(|_| invalid())?),
+                None => match directive.parse() {
+                    Ok(level) => {
+                        directives.default = Some(level);
+                        continue;
+                    }
+                    Err(_) => (directive, LevelFilter::Trace),
+                },
+            };
+            match name.strip_prefix("scope:") {
+                Some(scope) if !scope.is_empty() => {
+                    directives.scopes.push((scope.to_owned(), level));
+                }
+                None if is_module_path(name) => {
+                    directives.modules.push((name.to_owned(), level));
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(directives)
+    }
+}
+
+/// Whether a name is a valid module path (identifiers separated by `::`).
+fn is_module_path(name: &str) -> bool {
+    name.split("::").all(|identifier| {
+        identifier
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_alphabetic() || first == '_')
+            && identifier
+                .chars()
+                .all(|character| character.is_alphanumeric() || character == '_')
     })
 }
 
+impl Directives {
+    /// The maximal level of the messages logged by some module in the current thread, if not the default.
+    fn level(&self, module_path: &str) -> Option<LevelFilter> {
+        let scope_level = NAMED_SCOPES.with(|named_scopes| {
+            named_scopes.borrow().iter().rev().find_map(|named_scope| {
+                self.scopes
+                    .iter()
+                    .rev()
+                    .find(|(name, _)| *name == named_scope.name)
+                    .map(|(_, level)| *level)
+            })
+        });
+        scope_level.or_else(|| {
+            self.modules
+                .iter()
+                .filter(|(name, _)| {
+                    module_path
+                        .strip_prefix(name.as_str())
+                        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+                })
+                .max_by_key(|(name, _)| name.len())
+                .map(|(_, level)| *level)
+        })
+    }
+
+    /// The maximal level of all the messages.
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .chain(self.scopes.iter())
+            .map(|(_, level)| *level)
+            .chain(self.default)
+            .max()
+            .unwrap_or(LevelFilter::Off)
+    }
+}
+
+/// The `log::max_level()` in effect before any directives were set (`None` if no directives are set).
+///
+/// This is the default maximal level of directives which do not specify one, and is restored when the directives are
+/// cleared.
+static BASE_LEVEL: Mutex<Option<LevelFilter>> = parking_lot::const_mutex(None);
+
+/// Set per-module and per-scope maximal levels of log messages.
+///
+/// The default maximal level is taken from the directives, if specified, or is the `log::max_level()` in effect
+/// before any directives were set. This sets `log::max_level()` to the maximal level of all the directives, so the
+/// default level should be modified using [`LoggyHandle::set_level`] (instead of `log::set_max_level`). If `None`, this
+/// restores `log::max_level()` to the level in effect before any directives were set.
+#[allow(clippy::significant_drop_tightening)] // Serialize concurrent changes of the levels.
+pub fn set_directives(directives: Option<Directives>) {
+    let mut base_level = BASE_LEVEL.lock();
+    if let Some(mut directives) = directives {
+        let level = *base_level.get_or_insert_with(log::max_level);
+        directives.default.get_or_insert(level);
+        set_max_level(directives.max_level());
+        SETTINGS.write().directives = Some(Arc::new(directives));
+    } else {
+        if let Some(level) = base_level.take() {
+            set_max_level(level);
+        }
+        SETTINGS.write().directives = None;
+    }
+}
+
 /// An RAII scope for log messages and [`error`]s.
-pub struct Scope<'a> {
-    /// The previous scope in effect before this one.
-    previous: Option<NamedScope>,
+///
+/// The scope name may be either a `&'static str` (which is cheap) or an owned `String` (e.g., created using
+/// `format!`).
+///
+/// If any errors were reported in the scope, it panics when dropped (unless a different [`FailurePolicy`] was set).
+/// Use [`Scope::finish`] or [`Scope::try_with`] to get a [`ScopeFailed`] error instead.
+pub struct Scope {
+    /// The number of enclosing scopes (that is, the position of this scope in the stack of scopes).
+    depth: usize,
+
+    /// Whether the scope was already completed (by [`Scope::finish`]).
+    is_finished: bool,
+
+    /// The failure policy of this scope, if overriding the default.
+    failure_policy: Option<FailurePolicy>,
+}
+
+/// The error returned when a [`Scope`] completes after some errors were reported in it.
+#[derive(Clone, Debug)]
+pub struct ScopeFailed {
+    /// The scope name.
+    pub name: String,
+
+    /// The scope index (if any).
+    pub index: Option<isize>,
+
+    /// The number of errors reported in the scope.
+    pub errors: usize,
 
-    /// Ensure the scope name outlives the scope.
-    name_lifetime: PhantomData<&'a str>,
+    /// The messages of the errors reported in the scope.
+    pub messages: Vec<String>,
+
+    /// How the scope is shown in messages (possibly including the enclosing scopes, see [`set_scope_path`]).
+    path: String,
+}
+
+impl Display for ScopeFailed {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            formatter,
+            "{}: failed with {} error(s)",
+            self.path, self.errors
+        )
+    }
 }
 
-impl<'a> Scope<'a> {
+impl Error for ScopeFailed {}
+
+impl Scope {
     /// Create a new logging scope.
     #[must_use] // FLAKY TESTED
-    pub fn new(name: &'a str) -> Self {
-        let name_ptr: *const str = name;
-        let static_name_ref: &'static str = unsafe { &*name_ptr };
-        let next: NamedScope = NamedScope {
-            name: static_name_ref,
-            index: None,
-            errors: 0,
-        };
-        let previous = NAMED_SCOPE.with(|named_scope| named_scope.replace(Some(next)));
-        Scope {
-            previous,
-            name_lifetime: PhantomData,
-        }
+    pub fn new<Name: Into<Cow<'static, str>>>(name: Name) -> Self {
+        Self::push(name.into(), None)
     }
 
     /// Create a new logging scope with an index.
     #[must_use]
-    pub fn new_indexed(name: &'a str, index: isize) -> Self {
-        let name_ptr: *const str = name;
-        let static_name_ref: &'static str = unsafe { &*name_ptr };
-        let next: NamedScope = NamedScope {
-            name: static_name_ref,
-            index: Some(index),
-            errors: 0,
-        };
-        let previous = NAMED_SCOPE.with(|named_scope| named_scope.replace(Some(next)));
-        Scope {
-            previous,
-            name_lifetime: PhantomData,
-        }
+    pub fn new_indexed<Name: Into<Cow<'static, str>>>(name: Name, index: isize) -> Self {
+        Self::push(name.into(), Some(index))
+    }
+
+    fn push(name: Cow<'static, str>, index: Option<isize>) -> Self {
+        NAMED_SCOPES.with(|named_scopes| {
+            let mut named_scopes = named_scopes.borrow_mut();
+            named_scopes.push(NamedScope {
+                name,
+                index,
+                errors: Arc::default(),
+            });
+            Self {
+                depth: named_scopes.len() - 1,
+                is_finished: false,
+                failure_policy: None,
+            }
+        })
     }
 
     /// Execute some code with in a named scope.
-    pub fn with<T, Code: FnOnce() -> T>(name: &'a str, code: Code) -> T {
-        let _scope = Scope::new(name);
+    pub fn with<Name: Into<Cow<'static, str>>, T, Code: FnOnce() -> T>(
+        name: Name,
+        code: Code,
+    ) -> T {
+        let _scope = Self::new(name);
         code()
     }
 
     /// Execute some code with in a named indexed scope.
-    pub fn with_indexed<T, Code: FnOnce() -> T>(name: &'a str, index: isize, code: Code) -> T {
-        let _scope = Scope::new_indexed(name, index);
+    pub fn with_indexed<Name: Into<Cow<'static, str>>, T, Code: FnOnce() -> T>(
+        name: Name,
+        index: isize,
+        code: Code,
+    ) -> T {
+        let _scope = Self::new_indexed(name, index);
         code()
     }
+
+    /// Execute some code in a named scope, returning an error instead of panicking if it reported any errors.
+    ///
+    /// # Errors
+    ///
+    /// If any errors were reported in the scope.
+    pub fn try_with<Name: Into<Cow<'static, str>>, T, Code: FnOnce() -> T>(
+        name: Name,
+        code: Code,
+    ) -> Result<T, ScopeFailed> {
+        let scope = Self::new(name);
+        let result = code();
+        scope.finish().map(|()| result)
+    }
+
+    /// Execute some code in a named indexed scope, returning an error instead of panicking if it reported any errors.
+    ///
+    /// # Errors
+    ///
+    /// If any errors were reported in the scope.
+    pub fn try_with_indexed<Name: Into<Cow<'static, str>>, T, Code: FnOnce() -> T>(
+        name: Name,
+        index: isize,
+        code: Code,
+    ) -> Result<T, ScopeFailed> {
+        let scope = Self::new_indexed(name, index);
+        let result = code();
+        scope.finish().map(|()| result)
+    }
+
+    /// Set what to do if any errors were reported in the scope when it is dropped (overriding the default set by
+    /// [`set_failure_policy`]).
+    pub const fn set_failure_policy(&mut self, failure_policy: FailurePolicy) {
+        self.failure_policy = Some(failure_policy);
+    }
+
+    /// Complete the scope, returning an error instead of panicking if any errors were reported in it.
+    ///
+    /// # Errors
+    ///
+    /// If any errors were reported in the scope.
+    pub fn finish(mut self) -> Result<(), ScopeFailed> {
+        self.complete()
+    }
+
+    /// Remove the scope from the stack of scopes, returning an error if any errors were reported in it.
+    fn complete(&mut self) -> Result<(), ScopeFailed> {
+        self.is_finished = true;
+        NAMED_SCOPES.with(|named_scopes| {
+            let mut named_scopes = named_scopes.borrow_mut();
+            named_scopes.truncate(self.depth + 1);
+            let messages = std::mem::take(&mut *named_scopes.last().unwrap().errors.lock());
+            let result = if messages.is_empty() {
+                Ok(())
+            } else {
+                let mut path = String::new();
+                NamedScope::append_path_to(
+                    &named_scopes,
+                    Settings::current().scope_path,
+                    &mut path,
+                );
+                let current = named_scopes.last().unwrap();
+                Err(ScopeFailed {
+                    name: current.name.to_string(),
+                    index: current.index,
+                    errors: messages.len(),
+                    messages,
+                    path,
+                })
+            };
+            named_scopes.pop();
+            result
+        })
+    }
 }
 
-impl<'a> Drop for Scope<'a> {
+impl Drop for Scope {
     fn drop(&mut self) {
-        let current = NAMED_SCOPE
-            .with(|named_scope| named_scope.replace(self.previous))
-            .unwrap();
-        if current.errors > 0 && !panicking() {
-            current.index.map_or_else(
-                || {
-                    std::panic!(
-                        "{}: [ERROR] {}: failed with {} error(s)", // FLAKY TESTED
-                        Loggy::global().prefix,
-                        current.name,   // FLAKY TESTED
-                        current.errors  // FLAKY TESTED
-                    );
-                },
-                |index| {
-                    // BEGIN NOT TESTED
-                    std::panic!(
-                        "{}: [ERROR] {}@{}: failed with {} error(s)",
-                        Loggy::global().prefix,
-                        current.name,
-                        index,
-                        current.errors
-                    );
-                    // END NOT TESTED
-                },
-            );
+        if self.is_finished {
+            return;
+        }
+        if let Err(failed) = self.complete() {
+            if panicking() {
+                return;
+            }
+            let prefix =
+                Loggy::current().map_or_else(String::new, |loggy| format!("{}: ", loggy.prefix));
+            let failure_policy = self
+                .failure_policy
+                .or_else(|| Settings::current().failure_policy)
+                .unwrap_or_default();
+            match failure_policy {
+                FailurePolicy::Panic => std::panic!("{prefix}[ERROR] {failed}"),
+                // BEGIN NOT TESTED
+                FailurePolicy::Exit(status) => {
+                    write_output(&format!("{prefix}[ERROR] {failed}\n"));
+                    std::process::exit(status);
+                }
+                // END NOT TESTED
+                FailurePolicy::Continue => {}
+            }
+        }
+    }
+}
+
+/// A source of the current time, for the time stamps of log messages.
+pub trait Clock: Send + Sync {
+    /// The current time.
+    fn now(&self) -> DateTime<Utc>;
+}
+
+/// The real system clock, used by default.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now() // NOT TESTED
+    }
+}
+
+/// A fake clock for tests, which only changes when explicitly set or advanced.
+pub struct FakeClock {
+    /// The current (fake) time.
+    now: Mutex<DateTime<Utc>>,
+}
+
+impl Default for FakeClock {
+    /// A fake clock starting at the Unix epoch (1970-01-01 00:00:00 UTC).
+    fn default() -> Self {
+        Self::new(DateTime::UNIX_EPOCH)
+    }
+}
+
+impl FakeClock {
+    /// Create a fake clock starting at some time.
+    #[must_use]
+    pub const fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            now: parking_lot::const_mutex(now),
         }
     }
+
+    /// Set the current (fake) time.
+    pub fn set(&self, now: DateTime<Utc>) {
+        *self.now.lock() = now;
+    }
+
+    /// Advance the current (fake) time.
+    ///
+    /// # Panics
+    ///
+    /// If the duration is too large.
+    pub fn advance(&self, duration: std::time::Duration) {
+        *self.now.lock() += TimeDelta::from_std(duration).unwrap();
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        *self.now.lock()
+    }
+}
+
+/// Set the clock used for the time stamps of log messages (by default, the system clock).
+pub fn set_clock(clock: Arc<dyn Clock>) {
+    SETTINGS.write().clock = Some(clock); // NOT TESTED
 }
 
 /// Control the behavior of the `loggy` logger.
+///
+/// This is created using [`Loggy::builder`] (or [`builder!`]), so adding more options is not a breaking change.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
 pub struct Loggy {
     /// A prefix appended to each message.
     ///
@@ -356,20 +913,37 @@ pub struct Loggy {
 static TOTAL_THREADS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
 
 thread_local!(
-    static THREAD_ID: Cell<Option<usize>> = Cell::new(None);
-    static FORCE_PANIC: Cell<bool> = Cell::new(false);
+    static THREAD_ID: Cell<Option<usize>> = const { Cell::new(None) };
+    static FORCE_PANIC: Cell<bool> = const { Cell::new(false) };
 );
 
 impl Log for Loggy {
     fn enabled(&self, metadata: &Metadata<'_>) -> bool {
-        metadata.level() == Level::Error
-            || metadata.level() == Level::Debug
-            || metadata.level() <= log::max_level()
+        Settings::current().is_enabled(metadata)
     }
 
     fn log(&self, record: &Record<'_>) {
-        if self.enabled(record.metadata()) {
-            emit_message(record.level(), self.format_message(record).as_ref());
+        let current = INSTALLED.read().clone();
+        let installed = current.unwrap_or_else(|| {
+            INSTALLED
+                .write()
+                .get_or_insert_with(|| self.clone())
+                .clone()
+        });
+        let pending_fields = PENDING_FIELDS.with(RefCell::take);
+        let settings = Settings::current();
+        if settings.is_enabled(record.metadata()) {
+            let capture = CaptureHandle::current();
+            let loggy = capture
+                .as_ref()
+                .map_or(&installed, |capture| &capture.state.loggy);
+            emit_message(
+                loggy,
+                &settings,
+                record,
+                loggy.format_message(&settings, record).as_ref(),
+                pending_fields,
+            );
         }
     }
 
@@ -380,19 +954,48 @@ impl Log for Loggy {
     // END NOT TESTED
 }
 
+/// The configuration of the installed global logger (if it is a [`Loggy`]).
+///
+/// This is used (instead of the installed logger itself) to format the log messages, so it may be modified using the
+/// [`LoggyHandle`].
+static INSTALLED: RwLock<Option<Loggy>> = parking_lot::const_rwlock(None);
+
 impl Loggy {
-    fn global() -> &'static Self {
-        let logger_ptr: *const dyn Log = logger();
-        #[allow(clippy::cast_ptr_alignment)]
-        #[allow(clippy::ptr_as_ptr)]
-        unsafe {
-            &*(logger_ptr as *const Self)
-        }
+    /// Install this as the global logger.
+    ///
+    /// This is identical to invoking `log::set_logger`, but also registers the configuration so it is available even
+    /// before the first message is logged (e.g., for the panic message of a failed [`Scope`]). You still need to invoke
+    /// `log::set_max_level` to enable messages below the `Error` level.
+    ///
+    /// # Errors
+    ///
+    /// If a global logger was already installed.
+    pub fn install(&'static self) -> Result<LoggyHandle, SetLoggerError> {
+        set_logger(self)?;
+        *INSTALLED.write() = Some(self.clone());
+        Ok(LoggyHandle { _private: () })
+    }
+
+    /// The configuration of the installed global logger.
+    ///
+    /// This is `None` if the global logger is not a `Loggy` (or is one which was installed using `log::set_logger`
+    /// and has not logged any message yet).
+    #[must_use]
+    pub fn installed() -> Option<Self> {
+        INSTALLED.read().clone()
+    }
+
+    /// The configuration in effect for the current thread: the test configuration if it is capturing log messages,
+    /// otherwise the installed global logger (if any).
+    fn current() -> Option<Self> {
+        CaptureHandle::current()
+            .map(|capture| capture.state.loggy.clone())
+            .or_else(Self::installed)
     }
 
-    fn format_message(&self, record: &Record<'_>) -> String {
+    fn format_message(&self, settings: &Settings, record: &Record<'_>) -> String {
         let now = if self.show_time {
-            format!("{}", Utc::now().format("%F %T%.3f")) // NOT TESTED
+            format!("{}", settings.now().format("%F %T%.3f"))
         } else {
             String::new()
         };
@@ -406,35 +1009,51 @@ impl Loggy {
             if index > 0 {
                 level = level.to_lowercase();
             }
-            self.append_prefix(&mut buffer, now.as_ref(), level.as_ref(), record);
+            self.append_prefix(
+                &mut buffer,
+                settings.scope_path,
+                now.as_ref(),
+                level.as_ref(),
+                record,
+            );
             writeln!(&mut buffer, " {line}").unwrap();
         }
 
         buffer
     }
 
-    fn append_prefix(&self, mut message: &mut String, now: &str, level: &str, record: &Record<'_>) {
+    fn append_prefix(
+        &self,
+        mut message: &mut String,
+        scope_path: Option<ScopePath>,
+        now: &str,
+        level: &str,
+        record: &Record<'_>,
+    ) {
         message.push_str(self.prefix);
 
         if self.show_thread {
-            // BEGIN NOT TESTED
-            THREAD_ID.with(|thread_id_cell| {
-                if thread_id_cell.get().is_none() {
-                    let total_threads =
-                        TOTAL_THREADS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
-                    thread_id_cell.set(Some(total_threads));
-                }
-                let current_thread_id = thread_id_cell.get().unwrap();
-                write!(&mut message, "[{current_thread_id}]").unwrap();
-            });
-            // END NOT TESTED
+            let current_thread_id = CaptureHandle::current().map_or_else(
+                || {
+                    THREAD_ID.with(|thread_id_cell| {
+                        if thread_id_cell.get().is_none() {
+                            let total_threads =
+                                TOTAL_THREADS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                            thread_id_cell.set(Some(total_threads));
+                        }
+                        thread_id_cell.get().unwrap()
+                    })
+                },
+                |capture| capture.thread_index(),
+            );
+            write!(&mut message, "[{current_thread_id}]").unwrap();
         }
 
         message.push(':');
 
         if self.show_time {
-            message.push(' '); // NOT TESTED
-            message.push_str(now); // NOT TESTED
+            message.push(' ');
+            message.push_str(now);
         }
 
         write!(&mut message, " [{level}]").unwrap();
@@ -451,135 +1070,1227 @@ impl Loggy {
             .unwrap();
         }
 
-        let (scope, index) = NAMED_SCOPE.with(|named_scope| {
-            named_scope
-                .get() // FLAKY TESTED
-                .map_or((record.module_path().unwrap(), None), |scope| {
-                    (scope.name, scope.index)
-                })
-        });
-
-        if !scope.is_empty() {
-            if let Some(index) = index {
-                write!(&mut message, " {scope}@{index}:").unwrap();
-            } else {
-                write!(&mut message, " {scope}:").unwrap();
+        NAMED_SCOPES.with(|named_scopes| {
+            let named_scopes = named_scopes.borrow();
+            match (named_scopes.last(), scope_path) {
+                (None, _) => {
+                    let module_path = record.module_path().unwrap(); // FLAKY TESTED
+                    if !module_path.is_empty() {
+                        write!(&mut message, " {module_path}:").unwrap();
+                    }
+                }
+                (Some(named_scope), None) if named_scope.name.is_empty() => {} // NOT TESTED
+                (Some(_), _) => {
+                    message.push(' ');
+                    NamedScope::append_path_to(&named_scopes, scope_path, message);
+                    message.push(':');
+                }
             }
-        }
+        });
     }
 }
 
-lazy_static! { // FLAKY TESTED
-    /// The buffer capturing the log messages for assertions.
-    static ref LOG_BUFFER: Mutex<Cell<Option<String>>> = Mutex::new(Cell::new(None));
+/// Where to write the log messages (except for captured messages).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Output {
+    /// Write the messages to the standard error.
+    #[default]
+    Stderr,
 
-    /// Whether to mirror captured log messages to stderr.
-    static ref MIRROR_TO_STDERR: bool = std::env::var("LOGGY_MIRROR_TO_STDERR")
-        // BEGIN FLAKY TESTED
-        .map(|var| !var.is_empty())
-        .unwrap_or(false);
-    // END FLAKY TESTED
+    /// Write the messages to the standard output.
+    Stdout,
 }
 
-/// Whether we already setup loggy as the global logger.
-static DID_SET_LOGGER: AtomicBool = AtomicBool::new(false); // FLAKY TESTED
+/// A file to write the log messages to (except for captured messages), instead of or in addition to the [`Output`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFile {
+    /// The path of the log file.
+    pub path: PathBuf,
 
-/// Force the next error-level message to be emitted as a panic.
-#[doc(hidden)] // FLAKY TESTED
-pub fn force_panic() {
-    FORCE_PANIC.with(|force_panic| {
-        force_panic.set(true);
-    });
+    /// Whether to append to the file if it exists (otherwise, it is truncated).
+    pub append: bool,
+
+    /// Whether to create the parent directories of the file if they do not exist.
+    pub create_dirs: bool,
+
+    /// Whether to also write the messages to the output (by default, the standard error).
+    pub tee: bool,
 }
 
-/// Actually emit (or capture) a log message.
-fn emit_message(level: Level, message: &str) {
-    if level == Level::Debug {
-        eprint!("{message}"); // FLAKY TESTED
-        return; // FLAKY TESTED
+impl LogFile {
+    /// Write the log messages to a file, appending them to it if it exists, creating its parent directories if
+    /// needed, and without writing them to the output.
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        Self {
+            path: path.into(),
+            append: true,
+            create_dirs: true,
+            tee: false,
+        }
     }
 
-    if level == Level::Error {
-        if FORCE_PANIC.with(|force_panic| force_panic.replace(false)) {
-            std::panic!("{message}");
-        } else {
-            NAMED_SCOPE.with(|maybe_named_scope| {
-                if let Some(ref mut named_scope) = maybe_named_scope.get() {
-                    named_scope.errors += 1;
-                    maybe_named_scope.set(Some(*named_scope));
-                } else {
-                    std::panic!(
-                        "{}: error! called outside a named scope", // FLAKY TESTED
-                        Loggy::global().prefix,
-                    );
-                }
-            });
+    /// Open the file for writing the log messages.
+    fn open(&self) -> std::io::Result<OpenLogFile> {
+        if self.create_dirs {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
         }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(self.append)
+            .write(true)
+            .truncate(!self.append)
+            .open(&self.path)?;
+        Ok(OpenLogFile {
+            file,
+            tee: self.tee,
+        })
     }
+}
 
-    let mut log_buffer = LOG_BUFFER.lock();
-    log_buffer.get_mut().as_mut().map_or_else(
-        || eprint!("{message}"),
-        |buffer| {
-            if *MIRROR_TO_STDERR {
-                eprint!("{message}"); // FLAKY TESTED
-            }
-            buffer.push_str(message);
-        },
-    );
+/// An opened [`LogFile`].
+struct OpenLogFile {
+    /// The file to write the messages to.
+    file: File,
+
+    /// Whether to also write the messages to the output.
+    tee: bool,
 }
 
-/// RAII for capturing the log content.
-struct Capture {}
+/// Where to write the log messages (except for captured messages).
+struct Sink {
+    /// The output stream.
+    output: Output,
 
-impl Capture {
-    fn new() -> Self {
-        if !DID_SET_LOGGER.swap(true, std::sync::atomic::Ordering::Relaxed) {
-            set_logger(&Loggy {
-                prefix: "test",     // FLAKY TESTED
-                show_time: false,   // FLAKY TESTED
-                show_thread: false, // FLAKY TESTED
-            })
-            .unwrap();
-            set_max_level(LevelFilter::Trace);
-        }
+    /// The file to write the messages to, if any.
+    log_file: Option<OpenLogFile>,
+}
 
-        LOG_BUFFER.lock().set(Some(String::new()));
+/// Where to write the log messages (except for captured messages).
+static SINK: Mutex<Sink> = parking_lot::const_mutex(Sink {
+    output: Output::Stderr,
+    log_file: None,
+});
 
-        TOTAL_THREADS.store(0, std::sync::atomic::Ordering::Relaxed);
-        THREAD_ID.with(|thread_id_cell| thread_id_cell.set(None));
+/// Set (or clear) the file to write the log messages to (except for captured messages).
+///
+/// This replaces (and closes) the previous file, if any, which allows reopening the log file (e.g., when it is
+/// rotated). If `None`, the messages are written only to the output.
+///
+/// # Errors
+///
+/// If the file (or its parent directories) could not be created. In this case, the previous file remains in effect.
+pub fn set_log_file(log_file: Option<&LogFile>) -> std::io::Result<()> {
+    let opened = log_file.map(LogFile::open).transpose()?;
+    SINK.lock().log_file = opened;
+    Ok(())
+}
 
-        Self {}
+/// Write a (possibly multi-line) log message to the output and/or the log file.
+///
+/// Each message is written using a single write, while holding a lock, so messages emitted by different threads are
+/// never interleaved. Failures to write to the log file are ignored.
+#[allow(clippy::significant_drop_tightening)] // Keep the same order of messages in the log file and the output.
+fn write_output(message: &str) {
+    let mut sink = SINK.lock();
+    if let Some(log_file) = sink.log_file.as_mut() {
+        let _ = log_file.file.write_all(message.as_bytes());
+        if !log_file.tee {
+            return;
+        }
+    }
+    match sink.output {
+        Output::Stderr => eprint!("{message}"), // FLAKY TESTED
+        Output::Stdout => print!("{message}"),  // NOT TESTED
+    }
+}
+
+/// Collect the configuration of the `loggy` logger, and install it (see [`Loggy::builder`]).
+#[derive(Clone, Debug)]
+#[must_use]
+pub struct LoggyBuilder {
+    /// How to format the log messages.
+    loggy: Loggy,
+
+    /// The maximal level of the log messages.
+    level: LevelFilter,
+
+    /// Where to write the log messages.
+    output: Output,
+
+    /// How to show the path of nested scopes.
+    scope_path: Option<ScopePath>,
+
+    /// What to do when errors are reported.
+    failure_policy: FailurePolicy,
+
+    /// Per-module and per-scope maximal levels of the log messages.
+    directives: Option<Directives>,
+
+    /// The file to write the log messages to, if any.
+    log_file: Option<LogFile>,
+}
+
+/// A handle to the installed `loggy` logger (see [`LoggyBuilder::init`] and [`Loggy::install`]).
+///
+/// This allows modifying the configuration of the logger at run-time (e.g., following a configuration reload). Each
+/// modification is atomic, that is, each log message is formatted using either the old or the new configuration, even
+/// if other threads are logging at the same time.
+#[derive(Clone, Copy, Debug)]
+pub struct LoggyHandle {
+    /// Prevent creating handles other than by installing the logger.
+    _private: (),
+}
+
+impl LoggyHandle {
+    /// The configuration of the installed logger.
+    #[must_use]
+    #[allow(clippy::missing_panics_doc)] // The handle is only created when installing the logger.
+    pub fn loggy(&self) -> Loggy {
+        INSTALLED.read().clone().unwrap()
+    }
+
+    /// Modify the configuration of the installed logger.
+    ///
+    /// All the modifications done by the code are applied together.
+    pub fn update<Code: FnOnce(&mut Loggy)>(&self, code: Code) {
+        if let Some(loggy) = INSTALLED.write().as_mut() {
+            code(loggy);
+        }
+    }
+
+    /// Set the prefix of the log messages.
+    pub fn set_prefix(&self, prefix: &'static str) {
+        self.update(|loggy| loggy.prefix = prefix);
+    }
+
+    /// Set whether to include the date and time in the log messages.
+    pub fn set_show_time(&self, show_time: bool) {
+        self.update(|loggy| loggy.show_time = show_time);
+    }
+
+    /// Set whether to include the thread id in the log messages.
+    pub fn set_show_thread(&self, show_thread: bool) {
+        self.update(|loggy| loggy.show_thread = show_thread);
+    }
+
+    /// Set the (default) maximal level of the log messages.
+    ///
+    /// This is identical to invoking `log::set_max_level`, unless directives were set (see [`set_directives`]), in
+    /// which case this sets their default level.
+    #[allow(clippy::significant_drop_tightening)] // Serialize concurrent changes of the levels.
+    pub fn set_level(&self, level: LevelFilter) {
+        let mut base_level = BASE_LEVEL.lock();
+        let mut settings = SETTINGS.write();
+        match settings.directives.as_mut() {
+            Some(directives) => {
+                *base_level = Some(level);
+                let directives = Arc::make_mut(directives);
+                directives.default = Some(level);
+                set_max_level(directives.max_level());
+            }
+            None => set_max_level(level),
+        }
+    }
+
+    /// Set (or clear) per-module and per-scope maximal levels of the log messages.
+    ///
+    /// This is identical to invoking [`set_directives`].
+    pub fn set_directives(&self, directives: Option<Directives>) {
+        set_directives(directives);
+    }
+}
+
+/// Start collecting the configuration of the `loggy` logger, using the name of the invoking crate as the prefix.
+///
+/// This is identical to `loggy::Loggy::builder().prefix(env!("CARGO_PKG_NAME"))`.
+#[macro_export]
+macro_rules! builder {
+    () => {
+        loggy::Loggy::builder().prefix(env!("CARGO_PKG_NAME"))
+    };
+}
+
+impl Loggy {
+    /// Start collecting the configuration of the `loggy` logger.
+    ///
+    /// By default, the prefix is the name of the program's executable, the time and thread are not shown, the level is
+    /// `Info`, messages are written to the standard error, only the innermost scope is shown, and failures panic. The
+    /// name of the invoking crate is only available when compiling it, so use [`builder!`] to use it as the prefix
+    /// instead.
+    pub fn builder() -> LoggyBuilder {
+        LoggyBuilder {
+            loggy: Self {
+                prefix: *PROGRAM_NAME,
+                show_time: false,
+                show_thread: false,
+            },
+            level: LevelFilter::Info,
+            output: Output::Stderr,
+            scope_path: None,
+            failure_policy: FailurePolicy::Panic,
+            directives: None,
+            log_file: None,
+        }
+    }
+}
+
+/// The name of the program's executable.
+static PROGRAM_NAME: LazyLock<&'static str> = LazyLock::new(|| {
+    std::env::args_os()
+        .next()
+        .as_ref()
+        .and_then(|path| Path::new(path).file_stem())
+        .and_then(|name| name.to_str())
+        .map_or("loggy", |name| Box::leak(name.to_owned().into_boxed_str())) // FLAKY TESTED
+});
+
+impl LoggyBuilder {
+    /// Set the prefix of the log messages (typically, the name of the program).
+    pub const fn prefix(mut self, prefix: &'static str) -> Self {
+        self.loggy.prefix = prefix;
+        self
+    }
+
+    /// Set whether to include the date and time in the log messages.
+    pub const fn show_time(mut self, show_time: bool) -> Self {
+        self.loggy.show_time = show_time;
+        self
+    }
+
+    /// Set whether to include the thread id in the log messages.
+    pub const fn show_thread(mut self, show_thread: bool) -> Self {
+        self.loggy.show_thread = show_thread;
+        self
+    }
+
+    /// Set the maximal level of the log messages (errors are always emitted, and so are debug messages in debug
+    /// builds).
+    pub const fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Set where to write the log messages.
+    pub const fn output(mut self, output: Output) -> Self {
+        self.output = output;
+        self
+    }
+
+    /// Set how to show the path of nested scopes (see [`set_scope_path`]).
+    pub const fn scope_path(mut self, scope_path: Option<ScopePath>) -> Self {
+        self.scope_path = scope_path;
+        self
+    }
+
+    /// Set what to do when errors are reported (see [`set_failure_policy`]).
+    pub const fn failure_policy(mut self, failure_policy: FailurePolicy) -> Self {
+        self.failure_policy = failure_policy;
+        self
+    }
+
+    /// Set per-module and per-scope maximal levels of the log messages (see [`set_directives`]).
+    ///
+    /// The default level of the directives, if not specified, is the one set by [`LoggyBuilder::level`].
+    pub fn directives(mut self, directives: Directives) -> Self {
+        self.directives = Some(directives);
+        self
+    }
+
+    /// Write the log messages to a file (see [`set_log_file`]).
+    pub fn log_file(mut self, log_file: LogFile) -> Self {
+        self.log_file = Some(log_file);
+        self
+    }
+
+    /// Override the configuration using the `LOGGY_*` environment variables (see [`LoggyBuilder::try_init`]).
+    ///
+    /// Invalid values are ignored, and reported as warnings which are appended to a string.
+    fn with_environment(mut self, warnings: &mut String) -> Self {
+        if let Some(directives) = environment_value(
+            self.loggy.prefix,
+            warnings,
+            "LOGGY_LEVEL",
+            parse_level_directives,
+        ) {
+            self.directives = Some(directives);
+        }
+        if let Some(show_time) =
+            environment_value(self.loggy.prefix, warnings, "LOGGY_TIME", parse_flag)
+        {
+            self.loggy.show_time = show_time;
+        }
+        if let Some(show_thread) =
+            environment_value(self.loggy.prefix, warnings, "LOGGY_THREAD", parse_flag)
+        {
+            self.loggy.show_thread = show_thread;
+        }
+        if let Some(output) = environment_value(
+            self.loggy.prefix,
+            warnings,
+            "LOGGY_OUTPUT",
+            |value| match value.to_lowercase().as_str() {
+                "stderr" => Some(Output::Stderr),
+                "stdout" => Some(Output::Stdout),
+                _ => None,
+            },
+        ) {
+            self.output = output;
+        }
+        if let Some(path) = std::env::var_os("LOGGY_FILE")
+            .filter(|path| !path.is_empty())
+            .map(PathBuf::from)
+        {
+            self.log_file = Some(self.log_file.map_or_else(
+                || LogFile::new(path.clone()),
+                |log_file| LogFile {
+                    path: path.clone(),
+                    ..log_file
+                },
+            ));
+        }
+        self
+    }
+
+    /// The configuration of the log messages format, without installing the logger.
+    ///
+    /// This is useful for [`set_test_loggy`], or for installing the logger using [`Loggy::install`] or
+    /// `log::set_logger`. Only the prefix, time and thread settings are used (the environment variables are ignored).
+    #[must_use]
+    pub fn build(self) -> Loggy {
+        self.loggy
+    }
+
+    /// Install the logger.
+    ///
+    /// The configuration may be overridden by the following environment variables (if set to a non-empty value), so
+    /// it can be changed without rebuilding the program:
+    ///
+    /// * `LOGGY_LEVEL` - the maximal level of the log messages (`off`, `error`, `warn`, `info`, `debug` or `trace`),
+    ///   or more generally, per-module and per-scope [`Directives`] (e.g., `info,parser=trace,scope:link=warn`). Here
+    ///   each module path and scope name requires an explicit level, so a misspelled level is reported as invalid.
+    ///
+    /// * `LOGGY_TIME` - whether to include the date and time in the log messages (`1`, `true`, `yes` or `on`, or
+    ///   `0`, `false`, `no` or `off`).
+    ///
+    /// * `LOGGY_THREAD` - whether to include the thread id in the log messages (same values as `LOGGY_TIME`).
+    ///
+    /// * `LOGGY_OUTPUT` - where to write the log messages (`stderr` or `stdout`).
+    ///
+    /// * `LOGGY_FILE` - the path of a file to write the log messages to (see [`LogFile`]).
+    ///
+    /// Invalid values are ignored, and reported as warnings in the log output. There are no variables for the format
+    /// or the colors of the log messages, since the format is fixed, and the messages are never colored (they are meant
+    /// to be easily parsed by tools, and compared with the expected log in tests).
+    ///
+    /// # Errors
+    ///
+    /// If a global logger was already installed (in which case nothing is changed), or the log file could not be created
+    /// (in which case the logger is still installed, but only writes the messages to the output).
+    pub fn try_init(self) -> Result<LoggyHandle, InitFailed> {
+        let mut warnings = String::new();
+        let result = self.with_environment(&mut warnings).do_try_init();
+        if !warnings.is_empty() {
+            write_output(&warnings);
+        }
+        result
+    }
+
+    /// Install the logger after the environment overrides were applied, before reporting any warnings about them.
+    ///
+    /// The log file is only opened once the logger was successfully installed, so a failed call does not touch the
+    /// log file of an already installed logger.
+    fn do_try_init(self) -> Result<LoggyHandle, InitFailed> {
+        let builder = self;
+        set_boxed_logger(Box::new(builder.loggy.clone())).map_err(InitFailed::Installed)?;
+        *INSTALLED.write() = Some(builder.loggy);
+        set_max_level(builder.level);
+        if builder.directives.is_some() {
+            set_directives(builder.directives);
+        }
+        SINK.lock().output = builder.output;
+        set_scope_path(builder.scope_path);
+        set_failure_policy(builder.failure_policy);
+        SINK.lock().log_file = builder
+            .log_file
+            .as_ref()
+            .map(LogFile::open)
+            .transpose()
+            .map_err(InitFailed::LogFile)?;
+        Ok(LoggyHandle { _private: () })
+    }
+
+    /// Install the logger.
+    ///
+    /// # Panics
+    ///
+    /// If a global logger was already installed, or the log file could not be created.
+    #[must_use = "the handle may be dropped, but it is better to explicitly ignore it"]
+    pub fn init(self) -> LoggyHandle {
+        self.try_init().unwrap()
+    }
+}
+
+/// Why [`LoggyBuilder::try_init`] failed.
+#[derive(Debug)]
+pub enum InitFailed {
+    /// A global logger was already installed.
+    Installed(SetLoggerError),
+
+    /// The log file could not be created.
+    LogFile(std::io::Error),
+}
+
+impl Display for InitFailed {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Installed(error) => write!(formatter, "{error}"),
+            Self::LogFile(error) => write!(formatter, "failed to create the log file: {error}"),
+        }
+    }
+}
+
+impl Error for InitFailed {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Installed(_) => None,
+            Self::LogFile(error) => Some(error),
+        }
+    }
+}
+
+/// The value of a configuration environment variable, if it is set to a valid non-empty value.
+///
+/// Invalid values are reported by appending a warning (using the prefix of the log messages) to a string.
+fn environment_value<T, Parse: FnOnce(&str) -> Option<T>>(
+    prefix: &str,
+    warnings: &mut String,
+    name: &str,
+    parse: Parse,
+) -> Option<T> {
+    let value = std::env::var_os(name).filter(|value| !value.is_empty())?;
+    let parsed = value.to_str().and_then(|value| parse(value.trim()));
+    if parsed.is_none() {
+        writeln!(
+            warnings,
+            "{prefix}: [WARN] loggy: ignoring invalid {name} value: {}",
+            value.to_string_lossy()
+        )
+        .unwrap();
+    }
+    parsed
+}
+
+/// Parse the value of a level directives environment variable.
+///
+/// Unlike [`Directives::from_str`], this requires an explicit level for each module path and scope name, so a
+/// misspelled level (e.g., `warning`) is reported instead of being taken to be a module path.
+fn parse_level_directives(value: &str) -> Option<Directives> {
+    value
+        .split(',')
+        .map(str::trim)
+        .all(|directive| {
+            directive.is_empty()
+                || directive.contains('=')
+                || directive.parse::<LevelFilter>().is_ok()
+        })
+        .then(|| value.parse().ok())
+        .flatten()
+}
+
+/// Parse the value of a boolean environment variable.
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Whether to mirror captured log messages to stderr.
+static MIRROR_TO_STDERR: LazyLock<bool> = LazyLock::new(|| {
+    std::env::var("LOGGY_MIRROR_TO_STDERR").is_ok_and(|var| !var.is_empty()) // FLAKY TESTED
+});
+
+/// Whether to capture debug messages in tests by default.
+static CAPTURE_DEBUG: LazyLock<bool> = LazyLock::new(|| {
+    std::env::var("LOGGY_CAPTURE_DEBUG").is_ok_and(|var| !var.is_empty()) // FLAKY TESTED
+});
+
+/// Whether loggy is the global logger, installing it (once) if no global logger was installed yet.
+///
+/// If loggy was already installed (e.g., using the builder), this keeps its levels. Otherwise, this installs it and
+/// enables all the messages. Every capturing thread waits until this is done, so no messages are lost.
+static LOGGER_IS_LOGGY: LazyLock<bool> = LazyLock::new(|| {
+    if Loggy::installed().is_some() {
+        return true;
+    }
+    let installed = Loggy {
+        prefix: "test",
+        show_time: false,
+        show_thread: false,
+    }
+    .install()
+    .is_ok();
+    if installed {
+        set_max_level(LevelFilter::Trace);
+    }
+    installed || Loggy::installed().is_some()
+});
+
+/// Force the next error-level message to be emitted as a panic.
+#[doc(hidden)] // FLAKY TESTED
+pub fn force_panic() {
+    FORCE_PANIC.with(|force_panic| {
+        force_panic.set(true);
+    });
+}
+
+/// Actually emit (or capture) a log message.
+///
+/// The structured record is only collected if the message is captured.
+fn emit_message(
+    loggy: &Loggy,
+    settings: &Settings,
+    record: &Record<'_>,
+    message: &str,
+    pending_fields: Option<PendingFields>,
+) {
+    let capture = CaptureHandle::current();
+    let level = record.level();
+
+    if level == Level::Debug
+        && !capture
+            .as_ref()
+            .is_some_and(|capture| capture.state.capture_debug.load(Ordering::Relaxed))
+    {
+        write_output(message); // FLAKY TESTED
+        return; // FLAKY TESTED
+    }
+
+    if level == Level::Error {
+        if FORCE_PANIC.with(|force_panic| force_panic.replace(false)) {
+            std::panic!("{message}");
+        } else {
+            NAMED_SCOPES.with(|named_scopes| {
+                let named_scopes = named_scopes.borrow();
+                if let Some(named_scope) = named_scopes.last() {
+                    let text = record.args().to_string();
+                    if settings.scope_path.is_some() {
+                        for named_scope in &named_scopes[..named_scopes.len() - 1] {
+                            named_scope.errors.lock().push(text.clone());
+                        }
+                    }
+                    named_scope.errors.lock().push(text);
+                } else {
+                    match settings.failure_policy.unwrap_or_default() {
+                        FailurePolicy::Panic => std::panic!(
+                            "{}: error! called outside a named scope", // FLAKY TESTED
+                            loggy.prefix,
+                        ),
+                        // BEGIN NOT TESTED
+                        FailurePolicy::Exit(status) => {
+                            write_output(message);
+                            write_output(&format!(
+                                "{}: error! called outside a named scope\n",
+                                loggy.prefix
+                            ));
+                            std::process::exit(status);
+                        }
+                        // END NOT TESTED
+                        FailurePolicy::Continue => {}
+                    }
+                }
+            });
+        }
+    }
+
+    capture.map_or_else(
+        || write_output(message),
+        |capture| {
+            if *MIRROR_TO_STDERR {
+                eprint!("{message}"); // FLAKY TESTED
+            }
+            capture.state.log.lock().push_str(message);
+            capture
+                .state
+                .records
+                .lock()
+                .push(CapturedRecord::new(record, pending_fields));
+        },
+    );
+}
+
+/// A structured field of a log message, given after the `;` in [`log!`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Field {
+    /// The name of the field.
+    pub name: String,
+
+    /// The value of the field.
+    pub value: FieldValue,
+}
+
+/// The value of a structured [`Field`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldValue {
+    /// A simple value, formatted using `Display`.
+    Value(String),
+
+    /// A nested structure of fields.
+    Nested(Vec<Field>),
+}
+
+impl Field {
+    /// Create a simple field (used by the [`log!`] macro).
+    #[doc(hidden)]
+    #[must_use]
+    pub fn value(name: &str, value: String) -> Self {
+        Self {
+            name: name.to_owned(),
+            value: FieldValue::Value(value),
+        }
+    }
+
+    /// Create a nested field (used by the [`log!`] macro).
+    #[doc(hidden)]
+    #[must_use]
+    pub fn nested(name: &str, fields: Vec<Self>) -> Self {
+        Self {
+            name: name.to_owned(),
+            value: FieldValue::Nested(fields),
+        }
+    }
+
+    /// Append the human-readable lines describing some fields to a message.
+    fn append_all(fields: &[Self], indent: &mut String, message: &mut String) {
+        for field in fields {
+            match &field.value {
+                FieldValue::Value(value) => {
+                    write!(message, "\n{indent}{}: {value}", field.name).unwrap();
+                }
+                FieldValue::Nested(nested) => {
+                    write!(message, "\n{indent}{}:", field.name).unwrap();
+                    indent.push_str("  ");
+                    Self::append_all(nested, indent, message);
+                    indent.truncate(indent.len() - 2);
+                }
+            }
+        }
+    }
+}
+
+impl FieldValue {
+    /// Access the simple value (if this isn't a nested structure).
+    #[must_use]
+    pub fn as_value(&self) -> Option<&str> {
+        match self {
+            Self::Value(value) => Some(value),
+            Self::Nested(_) => None,
+        }
+    }
+
+    /// Access a field of a nested structure (if this is one, and it has such a field).
+    #[must_use]
+    pub fn field(&self, name: &str) -> Option<&Self> {
+        match self {
+            Self::Value(_) => None,
+            Self::Nested(fields) => find_field(fields, name),
+        }
+    }
+}
+
+/// Find a field by its name.
+fn find_field<'a>(fields: &'a [Field], name: &str) -> Option<&'a FieldValue> {
+    fields
+        .iter()
+        .find(|field| field.name == name)
+        .map(|field| &field.value)
+}
+
+/// The structured fields of the log message currently being emitted by the current thread.
+struct PendingFields {
+    /// The length of the message text preceding the fields.
+    message_len: usize,
+
+    /// The structured fields.
+    fields: Vec<Field>,
+}
+
+thread_local! { // FLAKY TESTED
+    static PENDING_FIELDS: RefCell<Option<PendingFields>> = const { RefCell::new(None) };
+}
+
+/// RAII for passing the structured fields of a message from the [`log!`] macro to the logger.
+///
+/// This is only used when the current thread is capturing log messages. Otherwise, the [`log!`] macro directly appends
+/// the fields to the message, without collecting them.
+#[doc(hidden)]
+pub struct FieldsGuard {}
+
+impl FieldsGuard {
+    /// Whether the structured fields need to be collected, that is, whether the current thread is capturing log
+    /// messages.
+    #[must_use]
+    pub fn is_capturing() -> bool {
+        CAPTURES.with(|captures| !captures.borrow().is_empty())
+    }
+
+    /// Append the fields to the message, and make them available to the logger.
+    #[must_use]
+    pub fn new(message: &mut String, fields: Vec<Field>) -> Self {
+        let message_len = message.len();
+        Field::append_all(&fields, &mut "  ".to_owned(), message);
+        PENDING_FIELDS.with(|pending_fields| {
+            pending_fields.replace(Some(PendingFields {
+                message_len,
+                fields,
+            }))
+        });
+        Self {}
+    }
+}
+
+impl Drop for FieldsGuard {
+    fn drop(&mut self) {
+        PENDING_FIELDS.with(RefCell::take);
+    }
+}
+
+/// A log message collected by [`capture_records`].
+#[derive(Clone, Debug)]
+pub struct CapturedRecord {
+    /// The level of the message.
+    pub level: Level,
+
+    /// The name of the scope the message was emitted in (if any).
+    pub scope: Option<String>,
+
+    /// The index of the scope the message was emitted in (if any).
+    pub scope_index: Option<isize>,
+
+    /// The path of the module the message was emitted in (if known).
+    pub module_path: Option<String>,
+
+    /// The source file the message was emitted in (if known).
+    pub file: Option<String>,
+
+    /// The line in the source file the message was emitted in (if known).
+    pub line: Option<u32>,
+
+    /// The message text, not including the structured fields.
+    pub message: String,
+
+    /// The structured fields of the message.
+    pub fields: Vec<Field>,
+}
+
+impl CapturedRecord {
+    fn new(record: &Record<'_>, pending_fields: Option<PendingFields>) -> Self {
+        let (scope, scope_index) = with_current_scope(|named_scope| {
+            named_scope.map_or((None, None), |named_scope| {
+                (Some(named_scope.name.to_string()), named_scope.index)
+            })
+        });
+        let mut message = record.args().to_string();
+        let fields = pending_fields.map_or_else(Vec::new, |pending_fields| {
+            message.truncate(pending_fields.message_len);
+            pending_fields.fields
+        });
+        Self {
+            level: record.level(),
+            scope,
+            scope_index,
+            module_path: record.module_path().map(str::to_owned),
+            file: record.file().map(str::to_owned),
+            line: record.line(),
+            message,
+            fields,
+        }
+    }
+
+    /// Access a (top-level) structured field by its name.
+    #[must_use]
+    pub fn field(&self, name: &str) -> Option<&FieldValue> {
+        find_field(&self.fields, name)
+    }
+}
+
+/// The state shared by all the threads contributing to a log capture.
+struct CaptureState {
+    /// The configuration used to format the captured log messages.
+    loggy: Loggy,
+
+    /// The captured log messages.
+    log: Mutex<String>,
+
+    /// The structured records of the captured log messages.
+    records: Mutex<Vec<CapturedRecord>>,
+
+    /// The threads which emitted messages into the capture, in order of appearance.
+    threads: Mutex<Vec<ThreadId>>,
+
+    /// The overrides of the global settings.
+    settings: RwLock<Settings>,
+
+    /// Whether to capture debug messages (instead of sending them to the log output).
+    capture_debug: AtomicBool,
+}
+
+thread_local! { // FLAKY TESTED
+    /// The (nested) log captures the current thread contributes to; messages go to the innermost one.
+    static CAPTURES: RefCell<Vec<CaptureHandle>> = const { RefCell::new(Vec::new()) };
+
+    /// Whether panics in the current thread are expected, so should not be reported by the panic hook.
+    static EXPECT_PANIC: Cell<bool> = const { Cell::new(false) };
+}
+
+/// A handle to the log capture of some test.
+///
+/// Log messages are only captured if they are emitted by the thread running the test, or by threads which were
+/// explicitly attached to its capture using [`CaptureHandle::with`] (or spawned using [`spawn`]). Messages emitted by
+/// any other thread are sent to the log output as usual. This allows multiple tests to capture their logs in
+/// parallel.
+///
+/// The global settings ([`set_clock`], [`set_scope_path`], [`set_failure_policy`] and [`set_directives`]) may be
+/// overridden for the threads contributing to a capture, so tests may use them without affecting other tests running
+/// in parallel.
+#[derive(Clone)]
+pub struct CaptureHandle {
+    /// The shared capture state.
+    state: Arc<CaptureState>,
+}
+
+impl CaptureHandle {
+    /// Access the capture the current thread contributes to (if any).
+    #[must_use]
+    pub fn current() -> Option<Self> {
+        CAPTURES.with(|captures| captures.borrow().last().cloned())
+    }
+
+    /// Execute some code, capturing its log messages in this capture.
+    ///
+    /// This is meant to be invoked in a worker thread, to tie it to the capture of the test that spawned it. If the
+    /// current thread is already contributing to some capture, this capture is nested inside it, that is, it will
+    /// capture the log messages until the code completes.
+    pub fn with<T, Code: FnOnce() -> T>(&self, code: Code) -> T {
+        let _capture = Capture::attach(self.clone());
+        code()
+    }
+
+    /// Override the clock (see [`set_clock`]) for this capture.
+    ///
+    /// This allows tests using `show_time` (see [`set_test_loggy`]) to use a [`FakeClock`] to have deterministic time
+    /// stamps.
+    pub fn set_clock(&self, clock: Arc<dyn Clock>) {
+        self.state.settings.write().clock = Some(clock);
+    }
+
+    /// Override how to show the path of nested scopes (see [`set_scope_path`]) for this capture, or use the global
+    /// setting if `None`.
+    pub fn set_scope_path(&self, scope_path: Option<ScopePath>) {
+        self.state.settings.write().scope_path = scope_path;
+    }
+
+    /// Override the default failure policy of scopes (see [`set_failure_policy`]) for this capture, or use the global
+    /// setting if `None`.
+    pub fn set_failure_policy(&self, failure_policy: Option<FailurePolicy>) {
+        self.state.settings.write().failure_policy = failure_policy;
+    }
+
+    /// Override the per-module and per-scope maximal levels (see [`set_directives`]) for this capture, or use the
+    /// global setting if `None`.
+    ///
+    /// Since all messages are captured up to the `Trace` level, the default level of the directives is `Trace` unless
+    /// specified.
+    pub fn set_directives(&self, directives: Option<Directives>) {
+        self.state.settings.write().directives = directives.map(Arc::new);
+    }
+
+    /// Set whether to capture debug messages (including [`todox!`] messages).
+    ///
+    /// By default, debug messages are not captured, and are always sent to the log output, unless the
+    /// `LOGGY_CAPTURE_DEBUG` environment variable is set to a non-empty value. Capturing them allows asserting that
+    /// a specific debug message was emitted.
+    pub fn set_capture_debug(&self, capture_debug: bool) {
+        self.state
+            .capture_debug
+            .store(capture_debug, Ordering::Relaxed);
+    }
+
+    /// Return the (capture-specific) index of the current thread.
+    fn thread_index(&self) -> usize {
+        let current_thread = std::thread::current().id();
+        let mut threads = self.state.threads.lock();
+        threads
+            .iter()
+            .position(|thread| *thread == current_thread)
+            .unwrap_or_else(|| {
+                threads.push(current_thread);
+                threads.len() - 1
+            })
+    }
+}
+
+/// Spawn a new thread whose log messages will be captured together with the current thread's messages.
+///
+/// This is identical to [`std::thread::spawn`] if the current thread isn't capturing log messages.
+pub fn spawn<T: Send + 'static, Code: FnOnce() -> T + Send + 'static>(code: Code) -> JoinHandle<T> {
+    let capture = CaptureHandle::current();
+    std::thread::spawn(move || match capture {
+        Some(capture) => capture.with(code),
+        None => code(), // NOT TESTED
+    })
+}
+
+/// The log context of a future: the log captures it contributes to and the scope it runs in.
+struct LogContext {
+    /// The (nested) log captures, as in [`CAPTURES`].
+    captures: Vec<CaptureHandle>,
+
+    /// The (nested) named scopes, as in [`NAMED_SCOPES`].
+    scopes: Vec<NamedScope>,
+}
+
+impl LogContext {
+    /// Take a copy of the log context of the current thread.
+    fn current() -> Self {
+        Self {
+            captures: CAPTURES.with(|captures| captures.borrow().clone()),
+            scopes: NAMED_SCOPES.with(|named_scopes| named_scopes.borrow().clone()),
+        }
+    }
+
+    /// Swap this log context with the log context of the current thread.
+    fn swap(&mut self) {
+        CAPTURES.with(|captures| std::mem::swap(&mut *captures.borrow_mut(), &mut self.captures));
+        NAMED_SCOPES
+            .with(|named_scopes| std::mem::swap(&mut *named_scopes.borrow_mut(), &mut self.scopes));
+    }
+}
+
+/// RAII for installing a log context in the current thread while polling a future.
+struct InstalledLogContext<'a> {
+    /// The log context of the future, which is swapped back into it when done.
+    context: &'a mut LogContext,
+}
+
+impl<'a> InstalledLogContext<'a> {
+    fn new(context: &'a mut LogContext) -> Self {
+        context.swap();
+        Self { context }
+    }
+}
+
+impl Drop for InstalledLogContext<'_> {
+    fn drop(&mut self) {
+        self.context.swap();
+    }
+}
+
+/// A future which carries its own log context (see [`with_log_context`]).
+pub struct WithLogContext<F: Future> {
+    /// The log context of the future.
+    context: LogContext,
+
+    /// The wrapped future (taken when it is dropped).
+    future: Option<Pin<Box<F>>>,
+}
+
+impl<F: Future> Future for WithLogContext<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<F::Output> {
+        let this = self.get_mut();
+        let _installed = InstalledLogContext::new(&mut this.context);
+        this.future.as_mut().unwrap().as_mut().poll(context)
+    }
+}
+
+impl<F: Future> Drop for WithLogContext<F> {
+    fn drop(&mut self) {
+        // A cancelled future may still hold scopes, which must be dropped in its own log context.
+        let future = self.future.take();
+        let _installed = InstalledLogContext::new(&mut self.context);
+        drop(future);
+    }
+}
+
+/// Attach the current log context (log captures and scope) to a future.
+///
+/// Each time the future is polled, the log context is installed in the polling thread, and when the poll completes,
+/// the (possibly modified) log context is detached from the thread and kept in the future. Therefore a [`Scope`]
+/// created inside the future survives `.await` points, even if other futures are polled by the same thread in between,
+/// or if the future is polled by different threads. Errors reported in a scope which was in effect when the future was
+/// created are counted by that scope.
+pub fn with_log_context<F: Future>(future: F) -> WithLogContext<F> {
+    WithLogContext {
+        context: LogContext::current(),
+        future: Some(Box::pin(future)),
+    }
+}
+
+/// Wake a thread blocked in [`block_on`].
+struct ThreadWaker {
+    /// The blocked thread.
+    thread: std::thread::Thread,
+}
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.thread.unpark();
+    }
+}
+
+/// Execute a future to completion in the current thread.
+///
+/// This is a minimal single-threaded executor, which allows using the log assertions (e.g. [`assert_logs_async`])
+/// without requiring an external async runtime.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = std::pin::pin!(future);
+    let waker = Waker::from(Arc::new(ThreadWaker {
+        thread: std::thread::current(),
+    }));
+    let mut context = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+/// The configuration used to format captured log messages.
+static TEST_LOGGY: Mutex<Loggy> = parking_lot::const_mutex(Loggy {
+    prefix: "test",
+    show_time: false,
+    show_thread: false,
+});
+
+/// Configure how to format the log messages captured by the log assertions (e.g., [`assert_logs`]).
+///
+/// By default, captured messages use the prefix `test`, without the time or thread. This configuration applies to the
+/// whole test binary, and is used by all captures started after it was set. Since tests run in parallel, it is best
+/// to invoke this at the start of each test (e.g., in a shared setup function), or to use the same configuration in
+/// all the tests of the same test binary.
+pub fn set_test_loggy(loggy: Loggy) {
+    *TEST_LOGGY.lock() = loggy;
+}
+
+/// The configuration used to format the log messages captured by the log assertions.
+#[must_use]
+pub fn test_loggy() -> Loggy {
+    TEST_LOGGY.lock().clone()
+}
+
+/// RAII for capturing the log content.
+struct Capture {
+    /// The capture we attached to.
+    handle: CaptureHandle,
+}
+
+impl Capture {
+    fn new() -> Self {
+        assert!(
+            *LOGGER_IS_LOGGY,
+            "loggy: can't capture log messages since a different global logger was installed"
+        );
+
+        Self::attach(CaptureHandle {
+            state: Arc::new(CaptureState {
+                loggy: test_loggy(),
+                log: Mutex::default(),
+                records: Mutex::default(),
+                threads: Mutex::default(),
+                settings: RwLock::default(),
+                capture_debug: AtomicBool::new(*CAPTURE_DEBUG),
+            }),
+        })
+    }
+
+    fn attach(handle: CaptureHandle) -> Self {
+        CAPTURES.with(|captures| captures.borrow_mut().push(handle.clone()));
+        Self { handle }
+    }
+
+    /// Take the log messages captured so far.
+    fn take_log(&self) -> String {
+        std::mem::take(&mut *self.handle.state.log.lock())
+    }
+
+    /// Take the structured records of the log messages captured so far.
+    fn take_records(&self) -> Vec<CapturedRecord> {
+        std::mem::take(&mut *self.handle.state.records.lock())
     }
 }
 
 impl Drop for Capture {
     fn drop(&mut self) {
-        LOG_BUFFER.lock().set(None);
+        let innermost = CAPTURES
+            .with(|captures| captures.borrow_mut().pop())
+            .unwrap();
+        debug_assert!(Arc::ptr_eq(&innermost.state, &self.handle.state));
     }
 }
 
-lazy_static! { // FLAKY TESTED
-    /// Ensure there is only a single test which is capturing log entries.
-    static ref SINGLE_TEST: Mutex<()> = Mutex::new(());
+/// Execute code that is expected to panic, without reporting the panic to the standard error.
+fn catch_expected_panic<Code: FnOnce() -> Result, Result>(
+    code: Code,
+) -> std::thread::Result<Result> {
+    static INSTALL_HOOK: Once = Once::new();
+    INSTALL_HOOK.call_once(|| {
+        let previous_hook = take_hook();
+        set_hook(Box::new(move |info| {
+            if !EXPECT_PANIC.with(Cell::get) {
+                previous_hook(info); // NOT TESTED
+            }
+        }));
+    });
+
+    let was_expecting = EXPECT_PANIC.with(|expect_panic| expect_panic.replace(true));
+    let result = catch_unwind(AssertUnwindSafe(code));
+    EXPECT_PANIC.with(|expect_panic| expect_panic.set(was_expecting));
+    result
+}
+
+/// Execute some code, returning its result together with the structured records of the log messages it emitted.
+///
+/// This allows filtering and examining specific messages, instead of comparing the whole log text as in
+/// [`assert_logs`]. The same rules apply for which messages are captured (see [`CaptureHandle`]).
+pub fn capture_records<T, Code: FnOnce() -> T>(code: Code) -> (T, Vec<CapturedRecord>) {
+    let capture = Capture::new();
+    let result = code();
+    (result, capture.take_records())
 }
 
 /// Assert that the collected log messages are as expected.
 ///
 /// The expected string is passed through `unindent` prior to the comparison, to enable proper indentation of the tests
-/// data in the code.
+/// data in the code. It may contain placeholders for nondeterministic parts of the log:
+///
+/// * `{*}` matches any text on the line.
+/// * `{time}` matches a time stamp (when using `show_time`).
+/// * `{thread}` matches a thread index (when using `show_thread`).
+/// * `{number}` matches an integer or a decimal number (possibly negative).
+/// * `{/regex/}` matches a regular expression (on a single line).
+/// * `{{` matches a literal `{`. Any other `{` is matched literally as well.
+///
+/// Log messages emitted by multiple threads may appear in a nondeterministic order. To allow for this, the expected
+/// messages between a `{unordered}` line and a `{/unordered}` line may appear in any order (but each multi-line message
+/// must still be contiguous, as is guaranteed by `loggy`). Each actual message is matched against the first unused
+/// expected message it matches, so list more specific expected messages before more general ones.
 ///
 /// # Notes
 ///
-/// The rust `log` facade mandates using a single global logger. Therefore, only one test can capture the log at any
-/// given time, using a global `Mutex`. Therefore, nesting this inside itself, [`assert_panics`] or
-/// [`assert_logs_panics`] will deadlock.
+/// Only log messages emitted by the current thread, or by threads attached to its capture (see [`CaptureHandle`]), are
+/// captured, so tests capturing their logs may run in parallel. Log assertions may be nested; the log messages emitted
+/// while an inner assertion is active are only captured by it, and not by the outer one.
 ///
 /// # Panics
 ///
 /// If the actual log is different from the expected log.
 pub fn assert_logs<Code: FnOnce() -> Result, Result>(expected_log: &str, code: Code) -> Result {
-    let _single_test = SINGLE_TEST.lock();
     do_assert_logs_panics(Some(expected_log), None, code).unwrap()
 }
 
@@ -595,15 +2306,14 @@ pub fn assert_logs<Code: FnOnce() -> Result, Result>(expected_log: &str, code: C
 ///
 /// # Notes
 ///
-/// The rust `log` facade mandates using a single global logger. Therefore, only one test can capture the log at any
-/// given time, using a global `Mutex`. Therefore, nesting this inside itself, [`assert_logs`] or
-/// [`assert_logs_panics`] will deadlock.
+/// Only log messages emitted by the current thread, or by threads attached to its capture (see [`CaptureHandle`]), are
+/// captured, so tests capturing their logs may run in parallel. Log assertions may be nested; the log messages emitted
+/// while an inner assertion is active are only captured by it, and not by the outer one.
 ///
 /// # Panics
 ///
 /// If the code does not panic, or panics with a different message than expected.
 pub fn assert_panics<Code: FnOnce() -> Result, Result>(expected_panic: &str, code: Code) {
-    let _single_test = SINGLE_TEST.lock();
     do_assert_logs_panics(None, Some(expected_panic), code);
 }
 
@@ -612,9 +2322,9 @@ pub fn assert_panics<Code: FnOnce() -> Result, Result>(expected_panic: &str, cod
 ///
 /// # Notes
 ///
-/// The rust `log` facade mandates using a single global logger. Therefore, only one test can capture the log at any
-/// given time, using a global `Mutex`. Therefore, nesting this inside itself, [`assert_panics`] or
-/// [`assert_logs_panics`] will deadlock.
+/// Only log messages emitted by the current thread, or by threads attached to its capture (see [`CaptureHandle`]), are
+/// captured, so tests capturing their logs may run in parallel. Log assertions may be nested; the log messages emitted
+/// while an inner assertion is active are only captured by it, and not by the outer one.
 ///
 /// # Panics
 ///
@@ -624,18 +2334,92 @@ pub fn assert_logs_panics<Code: FnOnce() -> Result, Result>(
     expected_panic: &str,
     code: Code,
 ) {
-    let _single_test = SINGLE_TEST.lock();
     do_assert_logs_panics(Some(expected_log), Some(expected_panic), code);
 }
 
+/// Similar to [`assert_panics`], but only require the panic message to match a regular expression.
+///
+/// The regular expression may match any part of the message; use `^` and `$` to match the whole message. This allows
+/// testing panics whose message contains addresses, paths, counts, etc.
+///
+/// # Panics
+///
+/// If the regular expression is invalid, or the code does not panic, or panics with a message that does not match the
+/// regular expression.
+pub fn assert_panics_matching<Code: FnOnce() -> Result, Result>(regex: &str, code: Code) {
+    do_assert_logs_panics_matching(None, regex, code);
+}
+
+/// Combine [`assert_logs`] and [`assert_panics_matching`], that is, assert that the expected log is generated and then
+/// a panic with a message matching the regular expression is triggered.
+///
+/// # Panics
+///
+/// If the regular expression is invalid, or the code does generate the expected log, or does not panic, or panics with
+/// a message that does not match the regular expression.
+pub fn assert_logs_panics_matching<Code: FnOnce() -> Result, Result>(
+    expected_log: &str,
+    regex: &str,
+    code: Code,
+) {
+    do_assert_logs_panics_matching(Some(expected_log), regex, code);
+}
+
+fn do_assert_logs_panics_matching<Code: FnOnce() -> Result, Result>(
+    expected_log: Option<&str>,
+    regex: &str,
+    code: Code,
+) {
+    let regex = Regex::new(regex).unwrap_or_else(|error| std::panic!("invalid regex: {error}")); // FLAKY TESTED
+    let (actual_panic, actual_log) = capture_log_panic(code);
+    do_assert_logs(expected_log, &actual_log);
+    let actual_panic = actual_panic.unwrap_or_else(|| std::panic!("test did not panic"));
+    assert!(
+        regex.is_match(&actual_panic),
+        "actual panic: {actual_panic} does not match the regex: {regex}"
+    );
+}
+
+/// Ensure that executing some code will panic with a payload of a specific type, which passes some check.
+///
+/// This allows testing panics triggered by `std::panic::panic_any` with a custom payload type. The payload of panics
+/// triggered using `panic!` is either a `String` or a `&'static str`, so it is better to use [`assert_panics`] or
+/// [`assert_panics_matching`] to test them.
+///
+/// # Panics
+///
+/// If the code does not panic, or panics with a payload of a different type, or the payload does not pass the check.
+pub fn assert_panics_with<
+    Payload: Any,
+    Check: FnOnce(&Payload) -> bool,
+    Code: FnOnce() -> Result,
+    Result,
+>(
+    check: Check,
+    code: Code,
+) {
+    let (payload, _actual_log) = capture_log_panic_payload(code);
+    let payload = payload.unwrap_or_else(|| std::panic!("test did not panic"));
+    let payload = payload.downcast_ref::<Payload>().unwrap_or_else(|| {
+        std::panic!(
+            "actual panic payload is not a {}",
+            std::any::type_name::<Payload>()
+        )
+    });
+    assert!(
+        check(payload),
+        "actual panic payload does not pass the check"
+    );
+}
+
 /// Similar to [`assert_logs_panics`], asserting that the `code` will generate one or more error messages, which will
 /// trigger a panic at the end of the `scope`.
 ///
 /// # Notes
 ///
-/// The rust `log` facade mandates using a single global logger. Therefore, only one test can capture the log at any
-/// given time, using a global `Mutex`. Therefore, nesting this inside itself, [`assert_panics`] or
-/// [`assert_logs_panics`] will deadlock.
+/// Only log messages emitted by the current thread, or by threads attached to its capture (see [`CaptureHandle`]), are
+/// captured, so tests capturing their logs may run in parallel. Log assertions may be nested; the log messages emitted
+/// while an inner assertion is active are only captured by it, and not by the outer one.
 ///
 /// # Panics
 ///
@@ -645,12 +2429,126 @@ pub fn assert_errors<Code: FnOnce() -> Result, Result>(
     expected_log: &str,
     code: Code,
 ) {
-    let expected_errors = expected_log.matches("[ERROR]").count();
+    do_assert_errors(scope, None, None, expected_log, code);
+}
+
+/// Similar to [`assert_errors`], but using an indexed scope (see [`Scope::with_indexed`]).
+///
+/// # Panics
+///
+/// If the code does generate the expected log, or the log does not contain errors (so the code does not panic).
+pub fn assert_indexed_errors<Code: FnOnce() -> Result, Result>(
+    scope: &str,
+    index: isize,
+    expected_log: &str,
+    code: Code,
+) {
+    do_assert_errors(scope, Some(index), None, expected_log, code);
+}
+
+/// Similar to [`assert_errors`], but explicitly specifying the number of expected errors.
+///
+/// By default, the number of expected errors is the number of times `[ERROR]` appears in the expected log. This is
+/// wrong if the log contains this text in other (non-error) lines.
+///
+/// # Panics
+///
+/// If the code does generate the expected log, or does not generate the expected number of errors.
+pub fn assert_errors_count<Code: FnOnce() -> Result, Result>(
+    scope: &str,
+    expected_errors: usize,
+    expected_log: &str,
+    code: Code,
+) {
+    do_assert_errors(scope, None, Some(expected_errors), expected_log, code);
+}
+
+/// Combine [`assert_indexed_errors`] and [`assert_errors_count`].
+///
+/// # Panics
+///
+/// If the code does generate the expected log, or does not generate the expected number of errors.
+pub fn assert_indexed_errors_count<Code: FnOnce() -> Result, Result>(
+    scope: &str,
+    index: isize,
+    expected_errors: usize,
+    expected_log: &str,
+    code: Code,
+) {
+    do_assert_errors(
+        scope,
+        Some(index),
+        Some(expected_errors),
+        expected_log,
+        code,
+    );
+}
+
+/// Similar to [`assert_logs`], but for an async `future` (executed using [`block_on`]).
+///
+/// The future carries the log capture (and any scope it creates) with it across `.await` points (see
+/// [`with_log_context`]).
+///
+/// # Panics
+///
+/// If the actual log is different from the expected log.
+pub fn assert_logs_async<F: Future>(expected_log: &str, future: F) -> F::Output {
+    assert_logs(expected_log, || block_on(with_log_context(future)))
+}
+
+/// Similar to [`assert_panics`], but for an async `future` (executed using [`block_on`]).
+///
+/// # Panics
+///
+/// If the future does not panic, or panics with a different message than expected.
+pub fn assert_panics_async<F: Future>(expected_panic: &str, future: F) {
+    assert_panics(expected_panic, || block_on(with_log_context(future)));
+}
+
+/// Similar to [`assert_logs_panics`], but for an async `future` (executed using [`block_on`]).
+///
+/// # Panics
+///
+/// If the future does generate the expected log, or does not panic, or panics with a different message than expected.
+pub fn assert_logs_panics_async<F: Future>(expected_log: &str, expected_panic: &str, future: F) {
+    assert_logs_panics(expected_log, expected_panic, || {
+        block_on(with_log_context(future))
+    });
+}
+
+/// Similar to [`assert_errors`], but for an async `future` (executed using [`block_on`]).
+///
+/// # Panics
+///
+/// If the future does generate the expected log, or the log does not contain errors (so the future does not panic).
+pub fn assert_errors_async<F: Future>(scope: &str, expected_log: &str, future: F) {
+    do_assert_errors(scope, None, None, expected_log, || {
+        block_on(with_log_context(future))
+    });
+}
+
+fn do_assert_errors<Code: FnOnce() -> Result, Result>(
+    scope: &str,
+    index: Option<isize>,
+    expected_errors: Option<usize>,
+    expected_log: &str,
+    code: Code,
+) {
+    let expected_errors =
+        expected_errors.unwrap_or_else(|| expected_log.matches("[ERROR]").count());
     assert!(expected_errors > 0, "expected log contains no errors");
+    let expected_scope = index.map_or_else(|| scope.to_owned(), |index| format!("{scope}@{index}"));
     assert_logs_panics(
         expected_log, // FLAKY TESTED
-        format!("test: [ERROR] {scope}: failed with {expected_errors} error(s)").as_str(), // FLAKY TESTED
-        || Scope::with(scope, code),
+        format!(
+            "{}: [ERROR] {expected_scope}: failed with {expected_errors} error(s)",
+            test_loggy().prefix
+        )
+        .as_str(), // FLAKY TESTED
+        || match index {
+            None => Scope::with(scope.to_owned(), code),
+            Some(index) => Scope::with_indexed(scope.to_owned(), index, code),
+        },
     );
 }
 
@@ -659,55 +2557,136 @@ fn do_assert_logs_panics<Code: FnOnce() -> Result, Result>(
     expected_panic: Option<&str>,
     code: Code,
 ) -> Option<Result> {
-    let _capture = Capture::new();
-
     if let Some(expected_panic) = expected_panic {
-        let prev_hook = take_hook();
-        set_hook(Box::new(|_| {}));
-        let result = catch_unwind(AssertUnwindSafe(code));
-        set_hook(prev_hook);
-
-        do_assert_logs(expected_log);
-
-        match result {
-            Ok(_) => std::panic!("test did not panic"), // FLAKY TESTED
-
-            Err(error) => {
-                #[allow(clippy::option_if_let_else)]
-                let actual_panic = if let Some(actual_panic) = error.downcast_ref::<String>() {
-                    actual_panic.as_str()
-                // BEGIN FLAKY TESTED
-                } else if let Some(actual_panic) = error.downcast_ref::<&'static str>() {
-                    actual_panic
-                } else {
-                    "unknown panic"
-                };
-                // END FLAKY TESTED
-                let expected_panic = fix_expected(expected_panic);
-                if actual_panic != expected_panic {
-                    // BEGIN NOT TESTED
-                    print!("ACTUAL PANIC:\n>>>\n{actual_panic}<<<\nIS DIFFERENT FROM EXPECTED PANIC:\n>>>\n{expected_panic}<<<\n");
-                    assert_eq!("ACTUAL PANIC", "EXPECTED PANIC");
-                } // END NOT TESTED
-            }
+        let (actual_panic, actual_log) = capture_log_panic(code);
+        do_assert_logs(expected_log, &actual_log);
+        let actual_panic = actual_panic.unwrap_or_else(|| std::panic!("test did not panic")); // FLAKY TESTED
+        let expected_panic = Expected::literal(&fix_expected(expected_panic));
+        if !expected_panic.is_match(&actual_panic) {
+            report_mismatch("PANIC", &expected_panic, &actual_panic);
         }
         None
     } else {
-        let result = code();
-        do_assert_logs(expected_log);
+        let (result, actual_log) = capture_log(code);
+        do_assert_logs(expected_log, &actual_log);
         Some(result)
     }
 }
 
-fn do_assert_logs(expected_log: Option<&str>) {
+fn do_assert_logs(expected_log: Option<&str>, actual_log: &str) {
     if let Some(expected_log) = expected_log {
-        let actual_log = LOG_BUFFER.lock().take().unwrap();
-        let expected_log = fix_expected(expected_log);
-        if actual_log != expected_log {
-            // BEGIN NOT TESTED
-            print!("ACTUAL LOG:\n>>>\n{actual_log}<<<\nIS DIFFERENT FROM EXPECTED LOG:\n>>>\n{expected_log}<<<\n");
-            assert_eq!("ACTUAL LOG", "EXPECTED LOG");
-        } // END NOT TESTED
+        let expected_log = Expected::parse(&fix_expected(expected_log));
+        if !expected_log.is_match(actual_log) {
+            report_mismatch("LOG", &expected_log, actual_log);
+        }
+    }
+}
+
+/// Execute some code, returning its result and the captured log.
+fn capture_log<Code: FnOnce() -> Result, Result>(code: Code) -> (Result, String) {
+    let capture = Capture::new();
+    let result = code();
+    (result, capture.take_log())
+}
+
+/// Execute some code that is expected to panic, returning the panic message (if it did panic) and the captured log.
+fn capture_log_panic<Code: FnOnce() -> Result, Result>(code: Code) -> (Option<String>, String) {
+    let (payload, actual_log) = capture_log_panic_payload(code);
+    (
+        payload.map(|payload| panic_message(payload.as_ref()).to_owned()),
+        actual_log,
+    )
+}
+
+/// Execute some code that is expected to panic, returning the panic payload (if it did panic) and the captured log.
+fn capture_log_panic_payload<Code: FnOnce() -> Result, Result>(
+    code: Code,
+) -> (Option<Box<dyn Any + Send>>, String) {
+    let capture = Capture::new();
+    let result = catch_expected_panic(code);
+    let actual_log = capture.take_log();
+    (result.err(), actual_log)
+}
+
+/// Extract the message from a panic payload.
+fn panic_message(error: &(dyn Any + Send)) -> &str {
+    #[allow(clippy::option_if_let_else)]
+    if let Some(actual_panic) = error.downcast_ref::<String>() {
+        actual_panic.as_str()
+    // BEGIN FLAKY TESTED
+    } else if let Some(actual_panic) = error.downcast_ref::<&'static str>() {
+        actual_panic
+    } else {
+        "unknown panic"
+    }
+    // END FLAKY TESTED
+}
+
+/// Assert that the collected log messages are identical to the content of a snapshot file.
+///
+/// The snapshot is stored in `tests/snapshots/<name>.log` (relative to the `CARGO_MANIFEST_DIR` of the tested crate).
+/// Like in [`assert_logs`], it may contain placeholders for nondeterministic parts of the log, but it is not
+/// unindented.
+///
+/// If the `LOGGY_BLESS` environment variable is set to a non-empty value, then instead of failing, the snapshot file is
+/// created or rewritten to contain the actual log. Snapshots which already match the actual log are not modified, so
+/// that placeholders added to them by hand are preserved.
+///
+/// # Panics
+///
+/// If the actual log is different from the snapshot (or the snapshot file is missing), unless blessing the snapshots.
+pub fn assert_logs_snapshot<Code: FnOnce() -> Result, Result>(name: &str, code: Code) -> Result {
+    let (result, actual_log) = capture_log(code);
+    assert_snapshot(name, "log", &actual_log, Expected::parse);
+    result
+}
+
+/// Combine [`assert_logs_snapshot`] and [`assert_panics`], using snapshot files for both the log and the panic.
+///
+/// The expected log is stored in `tests/snapshots/<name>.log` and the expected panic message is stored in
+/// `tests/snapshots/<name>.panic`.
+///
+/// As in [`assert_logs_snapshot`], setting the `LOGGY_BLESS` environment variable will create or rewrite both snapshot
+/// files instead of failing.
+///
+/// # Panics
+///
+/// If the code does not panic, or the actual log or panic message are different from the snapshots (or the snapshot
+/// files are missing), unless blessing the snapshots.
+pub fn assert_logs_panics_snapshot<Code: FnOnce() -> Result, Result>(name: &str, code: Code) {
+    let (actual_panic, actual_log) = capture_log_panic(code);
+    assert_snapshot(name, "log", &actual_log, Expected::parse);
+    let actual_panic = actual_panic.unwrap_or_else(|| std::panic!("test did not panic"));
+    assert_snapshot(name, "panic", &actual_panic, Expected::literal);
+}
+
+/// Compare the actual text with the content of a snapshot file (or bless it).
+fn assert_snapshot(name: &str, extension: &str, actual: &str, parse: fn(&str) -> Expected) {
+    let relative_path = format!("tests/snapshots/{name}.{extension}");
+    let path = std::env::var_os("CARGO_MANIFEST_DIR")
+        .map_or_else(PathBuf::new, PathBuf::from)
+        .join(&relative_path);
+    let expected = std::fs::read_to_string(&path).ok().map(|text| parse(&text));
+    if expected
+        .as_ref()
+        .is_some_and(|expected| expected.is_match(actual))
+    {
+        return;
+    }
+
+    if std::env::var("LOGGY_BLESS").is_ok_and(|var| !var.is_empty()) {
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).unwrap();
+        }
+        std::fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    match expected {
+        None => {
+            std::panic!("missing snapshot file: {relative_path} (set LOGGY_BLESS=1 to create it)")
+        }
+        Some(expected) => report_mismatch(&extension.to_uppercase(), &expected, actual),
     }
 }
 
@@ -722,13 +2701,90 @@ pub fn assert_writes<Code: FnOnce(&mut dyn IoWrite)>(expected_string: &str, code
     let mut actual_bytes: Vec<u8> = vec![];
     code(&mut actual_bytes);
     let actual_string = String::from_utf8(actual_bytes).ok().unwrap();
-    let expected_string = fix_expected(expected_string);
-    if actual_string != expected_string {
-        // BEGIN FLAKY TESTED
-        print!("ACTUAL WRITTEN:\n>>>\n{actual_string}<<<\nIS DIFFERENT FROM EXPECTED WRITTEN:\n>>>\n{expected_string}<<<\n");
-        assert_eq!("ACTUAL WRITTEN", "EXPECTED WRITTEN");
-        // END FLAKY TESTED
+    let expected_string = Expected::literal(&fix_expected(expected_string));
+    if !expected_string.is_match(&actual_string) {
+        report_mismatch("WRITTEN", &expected_string, &actual_string); // FLAKY TESTED
+    }
+}
+
+/// Check that some code writes the expected text to the standard error file descriptor.
+///
+/// TODO: This crate isn't really the best place for this.
+///
+/// This redirects file descriptor 2 itself while the code executes, so it captures anything written to it, including
+/// by child processes. Redirections may only be active in one thread at any given time, so concurrent calls are
+/// serialized, but they may be nested (e.g., an `assert_stderr` inside an `assert_stdout`).
+///
+/// Note that the Rust test harness intercepts `eprint!` and `eprintln!` in tests, so their output only reaches the file
+/// descriptor when running with `--nocapture`. Writing to `std::io::stderr()` directly is not intercepted.
+///
+/// # Panics
+///
+/// If the code does not write the expected text.
+#[cfg(unix)]
+pub fn assert_stderr<Code: FnOnce() -> Result, Result>(
+    expected_string: &str,
+    code: Code,
+) -> Result {
+    assert_fd_writes(2, "STDERR", expected_string, code)
+}
+
+/// Check that some code writes the expected text to the standard output file descriptor.
+///
+/// TODO: This crate isn't really the best place for this.
+///
+/// This redirects file descriptor 1 itself while the code executes, with the same caveats as `assert_stderr`.
+///
+/// # Panics
+///
+/// If the code does not write the expected text.
+#[cfg(unix)]
+pub fn assert_stdout<Code: FnOnce() -> Result, Result>(
+    expected_string: &str,
+    code: Code,
+) -> Result {
+    assert_fd_writes(1, "STDOUT", expected_string, code)
+}
+
+#[cfg(unix)]
+fn assert_fd_writes<Code: FnOnce() -> Result, Result>(
+    fd: std::os::unix::io::RawFd,
+    what: &str,
+    expected_string: &str,
+    code: Code,
+) -> Result {
+    let (result, actual_bytes) = redirect::capture_fd(fd, code);
+    let actual_string = String::from_utf8(actual_bytes).ok().unwrap();
+    let expected_string = Expected::literal(&fix_expected(expected_string));
+    if !expected_string.is_match(&actual_string) {
+        report_mismatch(what, &expected_string, &actual_string);
+    }
+    result
+}
+
+/// Whether to print the full actual text when it is different from the expected text.
+static SHOW_ACTUAL: LazyLock<bool> = LazyLock::new(|| {
+    std::env::var("LOGGY_SHOW_ACTUAL").is_ok_and(|var| !var.is_empty()) // FLAKY TESTED
+});
+
+/// Report the differences between the expected and actual text and fail the test.
+///
+/// Only a unified diff is printed, unless the `LOGGY_SHOW_ACTUAL` environment variable is set to a non-empty value, in
+/// which case the full actual text is printed as well. The diff is colored if the standard output is a terminal.
+fn report_mismatch(what: &str, expected: &Expected, actual: &str) {
+    if *SHOW_ACTUAL {
+        print!("ACTUAL {what}:\n>>>\n{actual}<<<\n"); // NOT TESTED
     }
+    let color = std::io::stdout().is_terminal();
+    print!(
+        "ACTUAL {what} IS DIFFERENT FROM EXPECTED {what}:\n{}",
+        expected.diff(actual, color)
+    );
+    std::panic!(
+        "actual {} is different from expected {}",
+        what.to_lowercase(),
+        what.to_lowercase()
+    );
 }
 
 fn fix_expected(expected: &str) -> String {
@@ -0,0 +1,81 @@
+// Copyright (C) 2017-2021 Oren Ben-Kiki. See the LICENSE.txt file at the top-level directory of this distribution and
+// at http://rust-lang.org/COPYRIGHT.
+//
+// Licensed under the MIT license <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your option. This file may not
+// be copied, modified, or distributed except according to those terms.
+
+//! Capturing everything written to the standard output or error file descriptors.
+
+use parking_lot::ReentrantMutex;
+use std::fs::File;
+use std::io::{stderr, stdout, Error, Read, Write};
+use std::os::unix::io::{FromRawFd, RawFd};
+
+/// Ensure only a single thread redirects file descriptors at any given time.
+///
+/// This is reentrant so redirections may be nested (e.g., capturing the standard error inside capturing the standard
+/// output, or even inside capturing it again).
+static REDIRECT: ReentrantMutex<()> = parking_lot::const_reentrant_mutex(());
+
+/// RAII for redirecting a file descriptor to a pipe.
+struct Redirect {
+    /// The redirected file descriptor.
+    fd: RawFd,
+
+    /// A duplicate of the original file descriptor, to restore when done.
+    saved_fd: RawFd,
+}
+
+impl Redirect {
+    /// Redirect the file descriptor to the write end of a new pipe, returning the read end of the pipe.
+    fn new(fd: RawFd) -> (Self, File) {
+        flush_std_streams();
+        let mut pipe_fds: [RawFd; 2] = [0; 2];
+        unsafe {
+            check(libc::pipe(pipe_fds.as_mut_ptr()));
+            let saved_fd = check(libc::dup(fd));
+            check(libc::dup2(pipe_fds[1], fd));
+            check(libc::close(pipe_fds[1]));
+            (Self { fd, saved_fd }, File::from_raw_fd(pipe_fds[0]))
+        }
+    }
+}
+
+impl Drop for Redirect {
+    fn drop(&mut self) {
+        flush_std_streams();
+        unsafe {
+            check(libc::dup2(self.saved_fd, self.fd));
+            check(libc::close(self.saved_fd));
+        }
+    }
+}
+
+/// Flush the Rust buffers of the standard streams so their content is written to the proper file descriptors.
+fn flush_std_streams() {
+    let _ = stdout().flush();
+    let _ = stderr().flush();
+}
+
+/// Panic if a system call failed.
+fn check(result: libc::c_int) -> libc::c_int {
+    assert!(result >= 0, "{}", Error::last_os_error()); // NOT TESTED
+    result
+}
+
+/// Execute some code, returning its result together with everything written to a file descriptor while it executed.
+///
+/// The reading is done by a separate thread, so the code may write any amount of data without blocking. Redirections
+/// are serialized using a global lock, so they may only be active in one thread at any given time (but may be nested).
+pub fn capture_fd<Code: FnOnce() -> Result, Result>(fd: RawFd, code: Code) -> (Result, Vec<u8>) {
+    let _redirect_lock = REDIRECT.lock();
+    let (redirect, mut reader) = Redirect::new(fd);
+    let reader_thread = std::thread::spawn(move || {
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes).unwrap();
+        bytes
+    });
+    let result = code();
+    drop(redirect);
+    (result, reader_thread.join().unwrap())
+}
@@ -0,0 +1,253 @@
+// FILE FLAKY TESTED
+
+#[macro_use]
+extern crate loggy;
+
+use loggy::{
+    assert_errors_async, assert_logs, assert_logs_async, assert_logs_panics_async,
+    assert_panics_async, block_on, with_log_context, Scope,
+};
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::Duration;
+
+/// Yield to the executor once.
+struct YieldNow {
+    did_yield: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        if self.did_yield {
+            Poll::Ready(())
+        } else {
+            self.did_yield = true;
+            context.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+const fn yield_now() -> YieldNow {
+    YieldNow { did_yield: false }
+}
+
+/// Complete after being woken by another thread.
+struct WakeFromThread {
+    is_done: Arc<AtomicBool>,
+}
+
+impl Future for WakeFromThread {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        if self.is_done.load(Ordering::Acquire) {
+            return Poll::Ready(());
+        }
+        let is_done = self.is_done.clone();
+        let waker = context.waker().clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(10));
+            is_done.store(true, Ordering::Release);
+            waker.wake();
+        });
+        Poll::Pending
+    }
+}
+
+/// Poll two futures alternately in the same thread until both complete.
+struct Join<First: Future, Second: Future> {
+    first: Pin<Box<First>>,
+    second: Pin<Box<Second>>,
+    first_done: bool,
+    second_done: bool,
+}
+
+impl<First: Future<Output = ()>, Second: Future<Output = ()>> Future for Join<First, Second> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        if !self.first_done {
+            self.first_done = self.first.as_mut().poll(context).is_ready();
+        }
+        if !self.second_done {
+            self.second_done = self.second.as_mut().poll(context).is_ready();
+        }
+        if self.first_done && self.second_done {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+fn join<First: Future<Output = ()>, Second: Future<Output = ()>>(
+    first: First,
+    second: Second,
+) -> Join<First, Second> {
+    Join {
+        first: Box::pin(first),
+        second: Box::pin(second),
+        first_done: false,
+        second_done: false,
+    }
+}
+
+/// Poll a future once, then drop it (cancelling it if it is still pending).
+struct PollOnce<F: Future> {
+    future: Option<Pin<Box<F>>>,
+}
+
+impl<F: Future> Future for PollOnce<F> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, context: &mut Context<'_>) -> Poll<()> {
+        let mut future = self.future.take().unwrap();
+        let _ = future.as_mut().poll(context);
+        Poll::Ready(())
+    }
+}
+
+fn poll_once<F: Future>(future: F) -> PollOnce<F> {
+    PollOnce {
+        future: Some(Box::pin(future)),
+    }
+}
+
+#[test]
+fn block_on_should_return_the_output() {
+    assert_eq!(
+        block_on(async {
+            yield_now().await;
+            1
+        }),
+        1
+    );
+}
+
+#[test]
+fn async_logs_should_be_captured() {
+    let result = assert_logs_async(
+        r#"
+        test: [INFO] scope: before
+        test: [INFO] scope: after
+        "#,
+        async {
+            let _scope = Scope::new("scope");
+            info!("before");
+            yield_now().await;
+            info!("after");
+            1
+        },
+    );
+    assert_eq!(result, 1);
+}
+
+#[test]
+fn async_logs_should_be_captured_when_woken_by_another_thread() {
+    assert_logs_async(
+        r#"
+        test: [INFO] scope: before
+        test: [INFO] scope: after
+        "#,
+        async {
+            let _scope = Scope::new("scope");
+            info!("before");
+            WakeFromThread {
+                is_done: Arc::new(AtomicBool::new(false)),
+            }
+            .await;
+            info!("after");
+        },
+    );
+}
+
+#[test]
+fn interleaved_futures_should_keep_their_scopes() {
+    assert_logs(
+        r#"
+        test: [INFO] first: one
+        test: [INFO] second: one
+        test: [INFO] first: two
+        test: [INFO] second: two
+        "#,
+        || {
+            block_on(join(
+                with_log_context(async {
+                    let _scope = Scope::new("first");
+                    info!("one");
+                    yield_now().await;
+                    info!("two");
+                }),
+                with_log_context(async {
+                    let _scope = Scope::new("second");
+                    info!("one");
+                    yield_now().await;
+                    info!("two");
+                }),
+            ));
+        },
+    );
+}
+
+#[test]
+fn async_panics_should_be_captured() {
+    assert_panics_async("test: [ERROR] test_async: foo\n", async {
+        yield_now().await;
+        panic!("foo");
+    });
+}
+
+#[test]
+fn async_logs_and_panics_should_be_captured() {
+    assert_logs_panics_async(
+        "test: [INFO] test_async: before\n",
+        "test: [ERROR] test_async: foo\n",
+        async {
+            info!("before");
+            yield_now().await;
+            panic!("foo");
+        },
+    );
+}
+
+#[test]
+fn async_errors_should_be_counted() {
+    assert_errors_async(
+        "scope",
+        r#"
+        test: [ERROR] scope: first
+        test: [ERROR] scope: second
+        "#,
+        async {
+            error!("first");
+            yield_now().await;
+            error!("second");
+        },
+    );
+}
+
+#[test]
+fn cancelled_futures_should_drop_their_scopes() {
+    assert_logs(
+        r#"
+        test: [INFO] inner: inside
+        test: [INFO] outer: after
+        "#,
+        || {
+            let _outer = Scope::new("outer");
+            block_on(poll_once(with_log_context(async {
+                let _inner = Scope::new("inner");
+                info!("inside");
+                std::future::pending::<()>().await;
+            })));
+            info!("after");
+        },
+    );
+}
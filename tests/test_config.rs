@@ -0,0 +1,110 @@
+// FILE FLAKY TESTED
+
+#[macro_use]
+extern crate loggy;
+
+use loggy::{
+    assert_errors, assert_logs, assert_panics, set_test_loggy, test_loggy, CaptureHandle,
+    FakeClock, Loggy,
+};
+use std::sync::Arc;
+use std::time::Duration;
+
+fn setup() {
+    set_test_loggy(
+        Loggy::builder()
+            .prefix("app")
+            .show_time(true)
+            .show_thread(true)
+            .build(),
+    );
+}
+
+#[test]
+fn configuration_should_be_used() {
+    setup();
+    assert_eq!(test_loggy().prefix, "app");
+    assert!(test_loggy().show_time);
+    assert!(test_loggy().show_thread);
+}
+
+#[test]
+fn installed_configuration_should_be_registered() {
+    setup();
+    assert_logs("", || {});
+    assert_eq!(Loggy::installed().unwrap().prefix, "test");
+}
+
+#[test]
+fn prefix_thread_and_time_should_be_shown() {
+    setup();
+    assert_logs(
+        r#"
+        app[0]: {time} [INFO] test_config: main
+        app[1]: {time} [INFO] test_config: worker
+        app[1]: {time} [info] test_config: continuation
+        app[0]: {time} [INFO] test_config: main again
+        "#,
+        || {
+            info!("main");
+            loggy::spawn(|| {
+                info!("worker\ncontinuation");
+            })
+            .join()
+            .unwrap();
+            info!("main again");
+        },
+    );
+}
+
+#[test]
+fn errors_should_use_prefix() {
+    setup();
+    assert_errors("scope", "app[0]: {time} [ERROR] scope: error\n", || {
+        error!("error");
+    });
+}
+
+#[test]
+fn panics_should_use_prefix() {
+    setup();
+    assert_panics("app: error! called outside a named scope", || {
+        error!("outsider");
+    });
+}
+
+#[test]
+fn fake_clock_should_be_used() {
+    setup();
+    let clock = Arc::new(FakeClock::default());
+    assert_logs(
+        r#"
+        app[0]: 1970-01-01 00:00:00.000 [INFO] test_config: start
+        app[0]: 1970-01-01 00:00:01.500 [INFO] test_config: later
+        app[0]: 1970-01-01 00:00:01.500 [info] test_config: continuation
+        app[1]: 1970-01-01 00:01:01.500 [INFO] test_config: worker
+        "#,
+        || {
+            CaptureHandle::current().unwrap().set_clock(clock.clone());
+            info!("start");
+            clock.advance(Duration::from_millis(1500));
+            info!("later\ncontinuation");
+            clock.advance(Duration::from_secs(60));
+            loggy::spawn(|| info!("worker")).join().unwrap();
+        },
+    );
+}
+
+#[test]
+fn fake_clock_should_be_settable() {
+    setup();
+    let clock = Arc::new(FakeClock::default());
+    clock.set("2021-02-03T04:05:06.789Z".parse().unwrap());
+    assert_logs(
+        "app[0]: 2021-02-03 04:05:06.789 [INFO] test_config: set\n",
+        || {
+            CaptureHandle::current().unwrap().set_clock(clock);
+            info!("set");
+        },
+    );
+}
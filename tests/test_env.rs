@@ -0,0 +1,42 @@
+// FILE FLAKY TESTED
+
+#[macro_use]
+extern crate loggy;
+
+use log::LevelFilter;
+use loggy::Loggy;
+use std::fs::{read_to_string, remove_dir_all};
+
+#[test]
+fn environment_should_override_the_builder() {
+    let directory = std::env::temp_dir().join(format!("loggy-test-env-{}", std::process::id()));
+    let path = directory.join("env.log");
+    let _ = remove_dir_all(&directory);
+
+    std::env::set_var("LOGGY_LEVEL", "debug");
+    std::env::set_var("LOGGY_TIME", "off");
+    std::env::set_var("LOGGY_THREAD", "Yes");
+    std::env::set_var("LOGGY_OUTPUT", "nowhere");
+    std::env::set_var("LOGGY_FILE", &path);
+
+    let handle = Loggy::builder()
+        .prefix("env")
+        .show_time(true)
+        .level(LevelFilter::Warn)
+        .init();
+    assert_eq!(handle.loggy().prefix, "env");
+    assert!(!handle.loggy().show_time);
+    assert!(handle.loggy().show_thread);
+    assert_eq!(log::max_level(), LevelFilter::Debug);
+
+    debug!("debug");
+    trace!("trace");
+
+    assert_eq!(
+        read_to_string(&path).unwrap(),
+        "env: [WARN] loggy: ignoring invalid LOGGY_OUTPUT value: nowhere\n\
+         env[0]: [DEBUG] tests/test_env.rs:32: test_env: debug\n"
+    );
+
+    remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,39 @@
+// FILE FLAKY TESTED
+
+#![cfg(unix)]
+
+#[macro_use]
+extern crate loggy;
+
+use log::LevelFilter;
+use loggy::Loggy;
+use std::ffi::OsStr;
+use std::fs::{read_to_string, remove_dir_all};
+use std::os::unix::ffi::OsStrExt;
+
+#[test]
+fn invalid_environment_should_be_reported() {
+    let directory =
+        std::env::temp_dir().join(format!("loggy-test-env-invalid-{}", std::process::id()));
+    let path = directory.join(OsStr::from_bytes(b"invalid-\xff.log"));
+    let _ = remove_dir_all(&directory);
+
+    std::env::set_var("LOGGY_LEVEL", "warning");
+    std::env::set_var("LOGGY_FILE", &path);
+
+    let _handle = Loggy::builder()
+        .prefix("env")
+        .level(LevelFilter::Info)
+        .init();
+    assert_eq!(log::max_level(), LevelFilter::Info);
+
+    info!("info");
+
+    assert_eq!(
+        read_to_string(&path).unwrap(),
+        "env: [WARN] loggy: ignoring invalid LOGGY_LEVEL value: warning\n\
+         env: [INFO] test_env_invalid: info\n"
+    );
+
+    remove_dir_all(&directory).unwrap();
+}
@@ -0,0 +1,79 @@
+// FILE FLAKY TESTED
+
+#[macro_use]
+extern crate loggy;
+
+use loggy::{set_log_file, LogFile, Loggy};
+use std::fs::{read_to_string, remove_dir_all};
+use std::thread;
+
+#[test]
+fn messages_should_be_written_to_the_log_file() {
+    let directory = std::env::temp_dir().join(format!("loggy-test-file-{}", std::process::id()));
+    let path = directory.join("nested").join("test.log");
+    let _ = remove_dir_all(&directory);
+
+    let _handle = Loggy::builder()
+        .prefix("file")
+        .log_file(LogFile {
+            append: false,
+            ..LogFile::new(&path)
+        })
+        .init();
+
+    info!("first");
+    let threads: Vec<_> = (0..4)
+        .map(|thread| {
+            thread::spawn(move || {
+                for index in 0..50 {
+                    info!("thread {thread} index {index}\nthread {thread} index {index}");
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    let log = read_to_string(&path).unwrap();
+    let mut lines = log.lines();
+    assert_eq!(lines.next(), Some("file: [INFO] test_file: first"));
+    for _ in 0..200 {
+        let first = lines.next().unwrap();
+        let second = lines.next().unwrap();
+        assert!(first.starts_with("file: [INFO] test_file: thread "));
+        assert_eq!(first.replace("[INFO]", "[info]"), second);
+    }
+    assert_eq!(lines.next(), None);
+
+    set_log_file(Some(&LogFile::new(&path))).unwrap();
+    info!("appended");
+    assert!(read_to_string(&path)
+        .unwrap()
+        .ends_with("file: [INFO] test_file: appended\n"));
+
+    assert!(set_log_file(Some(&LogFile::new(path.join("invalid")))).is_err());
+    set_log_file(Some(&LogFile {
+        append: false,
+        ..LogFile::new(&path)
+    }))
+    .unwrap();
+    let value = 1;
+    info!("truncated"; value, nested { other => 2 });
+    let truncated = "file: [INFO] test_file: truncated\n\
+                     file: [info] test_file:   value: 1\n\
+                     file: [info] test_file:   nested:\n\
+                     file: [info] test_file:     other: 2\n";
+    assert_eq!(read_to_string(&path).unwrap(), truncated);
+
+    set_log_file(None).unwrap();
+    info!("not in file");
+    assert_eq!(read_to_string(&path).unwrap(), truncated);
+
+    assert_eq!(
+        Loggy::builder().try_init().unwrap_err().to_string(),
+        "attempted to set a logger after the logging system was already initialized"
+    );
+
+    remove_dir_all(&directory).unwrap();
+}
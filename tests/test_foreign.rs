@@ -0,0 +1,56 @@
+// FILE FLAKY TESTED
+
+#[macro_use]
+extern crate loggy;
+
+use log::{LevelFilter, Log, Metadata, Record};
+use loggy::{assert_logs, Loggy, Scope};
+use std::panic::catch_unwind;
+use std::sync::Mutex;
+
+struct ForeignLogger {
+    messages: Mutex<Vec<String>>,
+}
+
+impl Log for ForeignLogger {
+    fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.messages
+            .lock()
+            .unwrap()
+            .push(format!("{}: {}", record.level(), record.args()));
+    }
+
+    fn flush(&self) {}
+}
+
+static FOREIGN_LOGGER: ForeignLogger = ForeignLogger {
+    messages: Mutex::new(Vec::new()),
+};
+
+#[test]
+fn foreign_logger_should_be_used() {
+    log::set_logger(&FOREIGN_LOGGER).unwrap();
+    log::set_max_level(LevelFilter::Info);
+
+    assert!(Loggy::installed().is_none());
+    Scope::with("scope", || {
+        info!("message");
+        error!("failure");
+    });
+    assert!(Loggy::installed().is_none());
+
+    assert_eq!(
+        *FOREIGN_LOGGER.messages.lock().unwrap(),
+        vec!["INFO: message", "ERROR: failure"]
+    );
+
+    let payload = catch_unwind(|| assert_logs("", || {})).unwrap_err();
+    assert_eq!(
+        *payload.downcast::<&str>().unwrap(),
+        "loggy: can't capture log messages since a different global logger was installed"
+    );
+}
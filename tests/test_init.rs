@@ -0,0 +1,84 @@
+// FILE FLAKY TESTED
+
+#[macro_use]
+extern crate loggy;
+
+use log::LevelFilter;
+use loggy::{assert_logs, assert_panics, FailurePolicy, LogFile, Loggy, Scope, ScopePath};
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+
+#[test]
+fn builder_should_install_the_logger() {
+    assert!(Loggy::builder().build().prefix.starts_with("test_init"));
+
+    let handle = builder!()
+        .show_time(true)
+        .level(LevelFilter::Warn)
+        .scope_path(Some(ScopePath::default()))
+        .failure_policy(FailurePolicy::Continue)
+        .init();
+    assert_eq!(handle.loggy().prefix, "loggy");
+    assert!(handle.loggy().show_time);
+    assert!(!handle.loggy().show_thread);
+    assert_eq!(Loggy::installed().unwrap().prefix, handle.loggy().prefix);
+    assert_eq!(log::max_level(), LevelFilter::Warn);
+
+    assert!(Loggy::builder().try_init().is_err());
+
+    let directory = std::env::temp_dir().join(format!("loggy-test-init-{}", std::process::id()));
+    let path = directory.join("init.log");
+    create_dir_all(&directory).unwrap();
+    write(&path, "content\n").unwrap();
+    assert!(Loggy::builder()
+        .log_file(LogFile {
+            append: false,
+            ..LogFile::new(&path)
+        })
+        .try_init()
+        .is_err());
+    assert_eq!(read_to_string(&path).unwrap(), "content\n");
+    remove_dir_all(&directory).unwrap();
+
+    handle.set_prefix("app");
+    handle.set_show_thread(true);
+    handle.update(|loggy| loggy.show_time = false);
+    handle.set_level(LevelFilter::Trace);
+    assert_eq!(handle.loggy().prefix, "app");
+    assert!(!handle.loggy().show_time);
+    assert!(handle.loggy().show_thread);
+    assert_eq!(Loggy::installed().unwrap().prefix, "app");
+    assert_eq!(log::max_level(), LevelFilter::Trace);
+
+    handle.set_level(LevelFilter::Info);
+    handle.set_directives(Some("parser=trace".parse().unwrap()));
+    assert_eq!(log::max_level(), LevelFilter::Trace);
+    handle.set_directives(Some("scope:noisy=debug".parse().unwrap()));
+    assert_eq!(log::max_level(), LevelFilter::Debug);
+    handle.set_directives(None);
+    assert_eq!(log::max_level(), LevelFilter::Info);
+
+    handle.set_directives(Some("scope:noisy=debug".parse().unwrap()));
+    handle.set_level(LevelFilter::Trace);
+    assert_eq!(log::max_level(), LevelFilter::Trace);
+    handle.set_level(LevelFilter::Warn);
+    assert_eq!(log::max_level(), LevelFilter::Debug);
+    handle.set_directives(None);
+    assert_eq!(log::max_level(), LevelFilter::Warn);
+
+    assert_panics("test: [ERROR] scope: failed with 1 error(s)", || {
+        let mut scope = Scope::new("scope");
+        scope.set_failure_policy(FailurePolicy::Panic);
+        error!("error");
+    });
+
+    Scope::with("outer", || {
+        Scope::with("inner", || error!("error"));
+    });
+
+    assert_logs("test: [ERROR] outer/inner: error\n", || {
+        Scope::with("outer", || {
+            Scope::with("inner", || error!("error"));
+        });
+    });
+    assert_eq!(log::max_level(), LevelFilter::Warn);
+}
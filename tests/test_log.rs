@@ -3,10 +3,14 @@
 #[macro_use]
 extern crate loggy;
 
+use log::{Level, LevelFilter};
 use loggy::{
-    assert_errors, assert_logs, assert_logs_panics, assert_panics, assert_writes, scope_errors,
-    Scope,
+    assert_errors, assert_errors_count, assert_indexed_errors, assert_indexed_errors_count,
+    assert_logs, assert_logs_panics, assert_logs_panics_matching, assert_panics,
+    assert_panics_matching, assert_panics_with, assert_writes, capture_records, scope_errors,
+    CaptureHandle, Directives, FailurePolicy, Field, FieldValue, Scope, ScopePath,
 };
+use std::sync::{Arc, Barrier};
 use std::thread;
 
 #[test]
@@ -37,6 +41,55 @@ fn error_inside_scope_is_captured() {
     assert_errors("scope", "test: [ERROR] scope: error\n", || error!("error"));
 }
 
+#[test]
+fn error_inside_indexed_scope_is_captured() {
+    assert_indexed_errors("scope", 7, "test: [ERROR] scope@7: error\n", || {
+        error!("error")
+    });
+}
+
+#[test]
+fn errors_are_counted_explicitly() {
+    assert_errors_count(
+        "scope",
+        1,
+        r#"
+        test: [WARN] scope: not an [ERROR]
+        test: [ERROR] scope: error
+        "#,
+        || {
+            warn!("not an [ERROR]");
+            error!("error");
+        },
+    );
+}
+
+#[test]
+fn indexed_errors_are_counted_explicitly() {
+    assert_indexed_errors_count(
+        "scope",
+        7,
+        2,
+        r#"
+        test: [ERROR] scope@7: error
+        test: [ERROR] scope@7: another error
+        "#,
+        || {
+            error!("error");
+            error!("another error");
+        },
+    );
+}
+
+#[test]
+fn errors_count_should_be_verified() {
+    assert_panics("actual panic is different from expected panic", || {
+        assert_errors_count("scope", 2, "test: [ERROR] scope: error\n", || {
+            error!("error");
+        });
+    });
+}
+
 #[test]
 fn error_outside_scope_is_panic() {
     assert_panics("test: error! called outside a named scope", || {
@@ -44,6 +97,45 @@ fn error_outside_scope_is_panic() {
     });
 }
 
+#[test]
+fn error_outside_scope_may_continue() {
+    assert_logs("test: [ERROR] test_log: outsider\n", || {
+        CaptureHandle::current()
+            .unwrap()
+            .set_failure_policy(Some(FailurePolicy::Continue));
+        error!("outsider");
+    });
+}
+
+#[test]
+fn failed_scope_may_continue() {
+    assert_logs("test: [ERROR] scope: error\n", || {
+        CaptureHandle::current()
+            .unwrap()
+            .set_failure_policy(Some(FailurePolicy::Continue));
+        Scope::with("scope", || {
+            error!("error");
+            assert_eq!(scope_errors(), 1);
+        });
+    });
+}
+
+#[test]
+fn scope_failure_policy_should_override_default() {
+    assert_logs_panics(
+        "test: [ERROR] scope: error\n",
+        "test: [ERROR] scope: failed with 1 error(s)",
+        || {
+            CaptureHandle::current()
+                .unwrap()
+                .set_failure_policy(Some(FailurePolicy::Continue));
+            let mut scope = Scope::new("scope");
+            scope.set_failure_policy(FailurePolicy::Panic);
+            error!("error");
+        },
+    );
+}
+
 #[test]
 fn scope_errors_outside_scope() {
     assert_eq!(scope_errors(), 0);
@@ -95,6 +187,211 @@ fn named_indexed_scope_should_replace_module() {
     });
 }
 
+#[test]
+fn owned_scope_names_should_be_used() {
+    let path = "input.txt";
+    assert_logs("test: [WARN] file input.txt@1: warning\n", || {
+        Scope::with_indexed(format!("file {path}"), 1, || {
+            warn!("warning");
+        })
+    });
+}
+
+#[test]
+fn nested_scopes_should_be_restored() {
+    assert_logs(
+        r#"
+        test: [INFO] outer: before
+        test: [INFO] inner: inside
+        test: [INFO] outer: after
+        test: [INFO] test_log: outside
+        "#,
+        || {
+            Scope::with("outer", || {
+                info!("before");
+                Scope::with(String::from("inner"), || info!("inside"));
+                info!("after");
+            });
+            info!("outside");
+        },
+    );
+}
+
+#[test]
+fn scope_paths_should_be_shown() {
+    assert_logs(
+        r#"
+        test: [INFO] build: start
+        test: [INFO] build/compile@3/link: inside
+        test: [INFO] test_log: outside
+        "#,
+        || {
+            CaptureHandle::current()
+                .unwrap()
+                .set_scope_path(Some(ScopePath::default()));
+            Scope::with("build", || {
+                info!("start");
+                Scope::with_indexed("compile", 3, || {
+                    Scope::with("link", || info!("inside"));
+                });
+            });
+            info!("outside");
+        },
+    );
+}
+
+#[test]
+fn scope_paths_should_be_limited() {
+    assert_logs("test: [INFO] compile@3 > link: inside\n", || {
+        CaptureHandle::current()
+            .unwrap()
+            .set_scope_path(Some(ScopePath {
+                separator: " > ",
+                depth: Some(2),
+            }));
+        Scope::with("build", || {
+            Scope::with_indexed("compile", 3, || {
+                Scope::with("link", || info!("inside"));
+            });
+        });
+    });
+}
+
+#[test]
+fn scope_path_errors_should_propagate() {
+    let show_scope_path = || {
+        CaptureHandle::current()
+            .unwrap()
+            .set_scope_path(Some(ScopePath::default()));
+    };
+    assert_panics("test: [ERROR] outer: failed with 1 error(s)", || {
+        show_scope_path();
+        Scope::with("outer", || {
+            assert_logs_panics(
+                "test: [ERROR] outer/inner: error\n",
+                "test: [ERROR] outer/inner: failed with 1 error(s)",
+                || {
+                    show_scope_path();
+                    Scope::with("inner", || error!("error"));
+                },
+            );
+            assert_eq!(scope_errors(), 1);
+        });
+    });
+}
+
+#[test]
+fn directives_should_be_parsed() {
+    assert_eq!(
+        " info, parser=trace ,scope:link=Warn,net::http,,"
+            .parse::<Directives>()
+            .unwrap(),
+        Directives {
+            default: Some(LevelFilter::Info),
+            modules: vec![
+                ("parser".to_owned(), LevelFilter::Trace),
+                ("net::http".to_owned(), LevelFilter::Trace),
+            ],
+            scopes: vec![("link".to_owned(), LevelFilter::Warn)],
+        }
+    );
+    assert_eq!(
+        "info,parser=loud"
+            .parse::<Directives>()
+            .unwrap_err()
+            .to_string(),
+        "invalid log level directive: parser=loud"
+    );
+    assert!("scope:=info".parse::<Directives>().is_err());
+    assert!("=info".parse::<Directives>().is_err());
+    assert!("net:http=info".parse::<Directives>().is_err());
+    assert!("my-crate".parse::<Directives>().is_err());
+}
+
+mod parser {
+    pub fn parse() {
+        trace!("parsing");
+        info!("parsed");
+    }
+}
+
+mod parser_extra {
+    pub fn parse() {
+        info!("extra");
+    }
+}
+
+#[test]
+fn directives_should_filter_messages() {
+    assert_logs(
+        r#"
+        test: [WARN] test_log: warning
+        test: [TRACE] test_log::parser: parsing
+        test: [INFO] test_log::parser: parsed
+        test: [INFO] link: linking
+        test: [INFO] link: parsed
+        test: [ERROR] build: error
+        "#,
+        || {
+            CaptureHandle::current().unwrap().set_directives(Some(
+                "warn,test_log::parser=trace,scope:link=info"
+                    .parse()
+                    .unwrap(),
+            ));
+            info!("ignored");
+            warn!("warning");
+            parser::parse();
+            parser_extra::parse();
+            Scope::with("link", || {
+                trace!("ignored");
+                info!("linking");
+                parser::parse();
+            });
+            Scope::try_with("build", || error!("error")).unwrap_err();
+        },
+    );
+}
+
+#[test]
+fn try_with_should_return_the_result() {
+    assert_logs("", || {
+        assert_eq!(Scope::try_with("scope", || 1).unwrap(), 1);
+    });
+}
+
+#[test]
+fn try_with_should_return_the_failure() {
+    assert_logs(
+        r#"
+        test: [ERROR] scope@7: first
+        test: [ERROR] scope@7: second
+        "#,
+        || {
+            let failed = Scope::try_with_indexed("scope", 7, || {
+                error!("first");
+                error!("second");
+            })
+            .unwrap_err();
+            assert_eq!(failed.name, "scope");
+            assert_eq!(failed.index, Some(7));
+            assert_eq!(failed.errors, 2);
+            assert_eq!(failed.messages, vec!["first", "second"]);
+            assert_eq!(failed.to_string(), "scope@7: failed with 2 error(s)");
+        },
+    );
+}
+
+#[test]
+fn finish_should_return_the_failure() {
+    assert_logs("test: [ERROR] scope: error\n", || {
+        let scope = Scope::new("scope");
+        error!("error");
+        let failed = scope.finish().unwrap_err();
+        assert_eq!(failed.messages, vec!["error"]);
+        assert_eq!(scope_errors(), 0);
+    });
+}
+
 #[test]
 fn multi_line_should_be_captured() {
     assert_logs(
@@ -129,6 +426,36 @@ fn debug_should_not_be_captured() {
     todox!("debug");
 }
 
+#[test]
+fn debug_should_be_captured_on_request() {
+    assert_logs(
+        r#"
+        test: [DEBUG] tests/test_log.rs:{number}: test_log: debug
+        test: [INFO] test_log: info
+        "#,
+        || {
+            CaptureHandle::current().unwrap().set_capture_debug(true);
+            debug!("debug");
+            info!("info");
+        },
+    );
+}
+
+#[test]
+fn debug_records_should_be_captured_on_request() {
+    let (_, records) = capture_records(|| {
+        CaptureHandle::current().unwrap().set_capture_debug(true);
+        todox!("debug"; value => 1);
+    });
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].level, Level::Debug);
+    assert_eq!(records[0].message, "debug");
+    assert_eq!(
+        records[0].field("value").and_then(FieldValue::as_value),
+        Some("1")
+    );
+}
+
 #[test]
 fn notice_should_be_captured() {
     assert_logs_panics(
@@ -167,7 +494,7 @@ fn worker_threads_should_be_reported() {
     "#,
         || {
             info!("before");
-            let child = thread::spawn(|| {
+            let child = loggy::spawn(|| {
                 info!("child");
             });
             child.join().unwrap();
@@ -176,6 +503,186 @@ fn worker_threads_should_be_reported() {
     );
 }
 
+#[test]
+fn unordered_worker_messages_should_match() {
+    assert_logs(
+        r#"
+        test: [INFO] test_log: before
+        {unordered}
+        test: [INFO] test_log: worker 0
+        test: [info] test_log: continuation 0
+        test: [INFO] test_log: worker 1
+        test: [info] test_log: continuation 1
+        test: [INFO] test_log: worker {number}
+        test: [info] test_log: continuation {number}
+        {/unordered}
+        test: [INFO] test_log: after
+        "#,
+        || {
+            info!("before");
+            let workers: Vec<_> = (0..3)
+                .map(|index| {
+                    loggy::spawn(move || {
+                        info!("worker {}\ncontinuation {}", index, index);
+                    })
+                })
+                .collect();
+            for worker in workers {
+                worker.join().unwrap();
+            }
+            info!("after");
+        },
+    );
+}
+
+#[test]
+fn unordered_messages_should_be_contiguous() {
+    assert_panics("actual log is different from expected log", || {
+        assert_logs(
+            r#"
+            {unordered}
+            test: [INFO] test_log: first
+            test: [info] test_log: continuation
+            test: [INFO] test_log: second
+            {/unordered}
+            "#,
+            || {
+                info!("first");
+                info!("second");
+                info!("continuation");
+            },
+        );
+    });
+}
+
+#[test]
+fn unordered_messages_should_all_appear() {
+    assert_panics("actual log is different from expected log", || {
+        assert_logs(
+            r#"
+            {unordered}
+            test: [INFO] test_log: first
+            test: [INFO] test_log: second
+            {/unordered}
+            "#,
+            || {
+                info!("second");
+                info!("third");
+            },
+        );
+    });
+}
+
+#[test]
+fn unordered_messages_may_contain_level_tags() {
+    assert_logs(
+        r#"
+        {unordered}
+        test: [INFO] test_log: first
+        test: [INFO] test_log: saw [error] tag
+        {/unordered}
+        "#,
+        || {
+            info!("saw [error] tag");
+            info!("first");
+        },
+    );
+}
+
+#[test]
+fn large_unordered_blocks_should_fail_quickly() {
+    assert_panics("actual log is different from expected log", || {
+        assert_logs(
+            r#"
+            {unordered}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            test: [INFO] test_log: worker {number}
+            {/unordered}
+            test: [INFO] test_log: done
+            "#,
+            || {
+                for index in 0..12 {
+                    info!("worker {index}");
+                }
+                info!("wrong");
+            },
+        );
+    });
+}
+
+#[test]
+fn attached_threads_should_be_reported() {
+    assert_logs(
+        r#"
+        test: [INFO] test_log: before
+        test: [INFO] test_log: child
+        test: [INFO] test_log: after
+    "#,
+        || {
+            info!("before");
+            let capture = CaptureHandle::current().unwrap();
+            let child = thread::spawn(move || {
+                capture.with(|| {
+                    info!("child");
+                });
+            });
+            child.join().unwrap();
+            info!("after");
+        },
+    );
+}
+
+#[test]
+fn unattached_threads_should_not_be_captured() {
+    assert_logs("test: [INFO] test_log: captured\n", || {
+        let child = thread::spawn(|| {
+            assert!(CaptureHandle::current().is_none());
+            info!("not captured");
+        });
+        child.join().unwrap();
+        info!("captured");
+    });
+}
+
+#[test]
+fn captures_should_run_in_parallel() {
+    let barrier = Arc::new(Barrier::new(2));
+    let threads: Vec<_> = (0..2)
+        .map(|index| {
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                assert_logs(
+                    format!(
+                        r#"
+                        test: [INFO] test_log: before {index}
+                        test: [INFO] test_log: after {index}
+                        "#
+                    )
+                    .as_str(),
+                    || {
+                        info!("before {}", index);
+                        barrier.wait();
+                        info!("after {}", index);
+                    },
+                );
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+}
+
 #[loggy::scope]
 fn scoped() {
     info!("message");
@@ -186,6 +693,42 @@ fn scoped_functions_should_work() {
     assert_logs("test: [INFO] scoped: message\n", scoped);
 }
 
+#[loggy::test]
+fn test_functions_without_options_should_not_be_scoped() {
+    assert_eq!(loggy::capture_records(|| info!("message")).1[0].scope, None);
+}
+
+#[loggy::test(logs = "test: [INFO] test_functions_should_assert_logs: message\n")]
+fn test_functions_should_assert_logs() {
+    info!("message");
+}
+
+#[loggy::test(panics = "test: [ERROR] test_functions_should_assert_panics: message\n")]
+fn test_functions_should_assert_panics() {
+    panic!("message");
+}
+
+#[loggy::test(
+    logs = "test: [INFO] test_functions_should_assert_logs_panics: message\n",
+    panics = "test: [ERROR] test_functions_should_assert_logs_panics: failure\n"
+)]
+fn test_functions_should_assert_logs_panics() {
+    info!("message");
+    panic!("failure");
+}
+
+#[loggy::test(
+    logs = r#"
+    test: [ERROR] test_functions_should_assert_errors: first
+    test: [ERROR] test_functions_should_assert_errors: second
+    "#,
+    errors = 2
+)]
+fn test_functions_should_assert_errors() {
+    error!("first");
+    error!("second");
+}
+
 #[loggy::scope("scope name")]
 fn name_scoped() {
     info!("message");
@@ -202,3 +745,246 @@ fn writes_are_captured() {
         writer.write_all("foo".as_bytes()).unwrap();
     });
 }
+
+#[test]
+fn nested_logs_should_be_captured_separately() {
+    assert_logs(
+        r#"
+        test: [INFO] test_log: outer before
+        test: [INFO] test_log: outer after
+        "#,
+        || {
+            info!("outer before");
+            assert_logs("test: [INFO] test_log: inner\n", || {
+                info!("inner");
+            });
+            info!("outer after");
+        },
+    );
+}
+
+#[test]
+fn logs_should_be_nested_in_panics() {
+    assert_logs_panics(
+        "test: [WARN] scope: outer\n",
+        "test: [ERROR] scope: failed with 1 error(s)",
+        || {
+            Scope::with("scope", || {
+                assert_logs("test: [ERROR] scope: inner\n", || {
+                    error!("inner");
+                });
+                warn!("outer");
+            });
+        },
+    );
+}
+
+#[test]
+fn panics_should_be_nested_in_panics() {
+    assert_panics("test: [ERROR] test_log: outer\n", || {
+        assert_panics("test: [ERROR] test_log: inner\n", || panic!("inner"));
+        panic!("outer");
+    });
+}
+
+#[test]
+fn records_should_be_captured() {
+    let (result, records) = capture_records(|| {
+        info!("simple");
+        Scope::with_indexed("scope", 7, || {
+            warn!("format {}", 0);
+        });
+        1
+    });
+    assert_eq!(result, 1);
+    assert_eq!(records.len(), 2);
+
+    assert_eq!(records[0].level, Level::Info);
+    assert_eq!(records[0].scope, None);
+    assert_eq!(records[0].scope_index, None);
+    assert_eq!(records[0].module_path.as_deref(), Some("test_log"));
+    assert_eq!(records[0].file.as_deref(), Some("tests/test_log.rs"));
+    assert!(records[0].line.is_some());
+    assert_eq!(records[0].message, "simple");
+    assert!(records[0].fields.is_empty());
+
+    assert_eq!(records[1].level, Level::Warn);
+    assert_eq!(records[1].scope.as_deref(), Some("scope"));
+    assert_eq!(records[1].scope_index, Some(7));
+    assert_eq!(records[1].message, "format 0");
+}
+
+#[test]
+fn structured_records_should_be_captured() {
+    let baz = 2;
+    let (_, records) = capture_records(|| {
+        trace!("both {}", 0; foo => 1, bar { baz }, qux => "text");
+    });
+    assert_eq!(records.len(), 1);
+    assert_eq!(records[0].message, "both 0");
+    assert_eq!(
+        records[0].fields,
+        vec![
+            Field {
+                name: "foo".to_owned(),
+                value: FieldValue::Value("1".to_owned()),
+            },
+            Field {
+                name: "bar".to_owned(),
+                value: FieldValue::Nested(vec![Field {
+                    name: "baz".to_owned(),
+                    value: FieldValue::Value("2".to_owned()),
+                }]),
+            },
+            Field {
+                name: "qux".to_owned(),
+                value: FieldValue::Value("text".to_owned()),
+            },
+        ]
+    );
+    assert_eq!(
+        records[0].field("foo").and_then(FieldValue::as_value),
+        Some("1")
+    );
+    assert_eq!(
+        records[0]
+            .field("bar")
+            .and_then(|bar| bar.field("baz"))
+            .and_then(FieldValue::as_value),
+        Some("2")
+    );
+    assert_eq!(records[0].field("bar").unwrap().as_value(), None);
+    assert_eq!(records[0].field("baz"), None);
+}
+
+#[test]
+fn placeholders_should_match_nondeterministic_text() {
+    assert_logs(
+        r#"
+        test: [INFO] test_log: path {*}/file.rs
+        test: [INFO] test_log: at {time} took {number} seconds
+        test: [INFO] test_log: thread [{thread}]
+        test: [INFO] test_log: map {/\{(\w+: \d+(, )?)+\}/}
+        test: [INFO] test_log: literal {{*} and {other}
+        "#,
+        || {
+            info!("path /some/where/file.rs");
+            info!("at 2021-01-02 03:04:05.678 took -1.5 seconds");
+            info!("thread [12]");
+            info!("map {{b: 2, a: 1}}");
+            info!("literal {{*}} and {{other}}");
+        },
+    );
+}
+
+#[test]
+fn placeholders_should_not_match_other_text() {
+    assert_panics("actual log is different from expected log", || {
+        assert_logs("test: [INFO] test_log: took {number} seconds\n", || {
+            info!("took some seconds");
+        });
+    });
+}
+
+#[test]
+fn different_logs_should_fail() {
+    assert_panics("actual log is different from expected log", || {
+        assert_logs(
+            r#"
+            test: [INFO] test_log: 1
+            test: [INFO] test_log: 2
+            test: [INFO] test_log: 3
+            test: [INFO] test_log: 4
+            test: [INFO] test_log: 5
+            test: [INFO] test_log: 6
+            test: [INFO] test_log: 7
+            test: [INFO] test_log: 8
+            test: [INFO] test_log: 9
+            "#,
+            || {
+                for index in 1..10 {
+                    if index == 2 {
+                        info!("two");
+                    } else if index != 8 {
+                        info!("{}", index);
+                    }
+                }
+                info!("10");
+            },
+        );
+    });
+}
+
+#[test]
+fn different_panics_should_fail() {
+    assert_panics("actual panic is different from expected panic", || {
+        assert_panics("expected", || std::panic!("actual"));
+    });
+}
+
+#[test]
+fn different_writes_should_fail() {
+    assert_panics("actual written is different from expected written", || {
+        assert_writes("expected", |writer| {
+            writer.write_all("actual".as_bytes()).unwrap();
+        });
+    });
+}
+
+#[test]
+fn panics_should_match_regex() {
+    assert_panics_matching(r"^failed after \d+ attempts at 0x[0-9a-f]+$", || {
+        std::panic!("failed after 3 attempts at 0x1f");
+    });
+}
+
+#[test]
+fn logs_and_panics_should_match_regex() {
+    assert_logs_panics_matching("test: [INFO] test_log: trying\n", r"attempts", || {
+        info!("trying");
+        std::panic!("failed after 3 attempts");
+    });
+}
+
+#[test]
+fn panics_should_not_match_other_regex() {
+    assert_panics(
+        "actual panic: failed does not match the regex: succeeded",
+        || {
+            assert_panics_matching("succeeded", || std::panic!("failed"));
+        },
+    );
+}
+
+#[derive(Debug)]
+struct CustomPayload {
+    code: i32,
+}
+
+#[test]
+fn panics_should_pass_payload_check() {
+    assert_panics_with(
+        |payload: &CustomPayload| payload.code == 7,
+        || std::panic::panic_any(CustomPayload { code: 7 }),
+    );
+}
+
+#[test]
+fn panics_should_fail_payload_check() {
+    assert_panics("actual panic payload does not pass the check", || {
+        assert_panics_with(
+            |payload: &CustomPayload| payload.code == 0,
+            || std::panic::panic_any(CustomPayload { code: 7 }),
+        );
+    });
+}
+
+#[test]
+fn panics_should_have_payload_type() {
+    assert_panics(
+        "actual panic payload is not a test_log::CustomPayload",
+        || {
+            assert_panics_with(|_: &CustomPayload| true, || std::panic!("message"));
+        },
+    );
+}
@@ -0,0 +1,59 @@
+// FILE FLAKY TESTED
+
+#![cfg(unix)]
+
+use loggy::{assert_panics, assert_stderr, assert_stdout};
+use std::io::Write;
+use std::process::Command;
+
+#[test]
+fn stderr_writes_are_captured() {
+    assert_stderr("foo\nbar\n", || {
+        std::io::stderr().write_all(b"foo\n").unwrap();
+        Command::new("sh")
+            .args(["-c", "echo bar >&2"])
+            .status()
+            .unwrap();
+    });
+}
+
+#[test]
+fn stdout_writes_are_captured() {
+    let result = assert_stdout(
+        r#"
+        foo
+        bar
+        "#,
+        || {
+            std::io::stdout().write_all(b"foo\n").unwrap();
+            Command::new("sh")
+                .args(["-c", "echo bar"])
+                .status()
+                .unwrap();
+            1
+        },
+    );
+    assert_eq!(result, 1);
+}
+
+#[test]
+fn different_stderr_should_fail() {
+    assert_panics("actual stderr is different from expected stderr", || {
+        assert_stderr("expected", || {
+            std::io::stderr().write_all(b"actual").unwrap();
+        });
+    });
+}
+
+#[test]
+fn nested_writes_are_captured() {
+    assert_stdout("outer\n", || {
+        assert_stderr("middle\n", || {
+            assert_stderr("inner\n", || {
+                std::io::stderr().write_all(b"inner\n").unwrap();
+            });
+            std::io::stderr().write_all(b"middle\n").unwrap();
+        });
+        std::io::stdout().write_all(b"outer\n").unwrap();
+    });
+}
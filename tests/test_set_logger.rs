@@ -0,0 +1,23 @@
+// FILE FLAKY TESTED
+
+#[macro_use]
+extern crate loggy;
+
+use log::LevelFilter;
+use loggy::{Loggy, Scope};
+use std::sync::LazyLock;
+
+static LOGGY: LazyLock<Loggy> = LazyLock::new(|| Loggy::builder().prefix("raw").build());
+
+#[test]
+fn raw_set_logger_should_register_on_first_message() {
+    log::set_logger(&*LOGGY).unwrap();
+    log::set_max_level(LevelFilter::Info);
+
+    assert!(Loggy::installed().is_none());
+    info!("message");
+    assert_eq!(Loggy::installed().unwrap().prefix, "raw");
+
+    Scope::with("scope", || info!("inside"));
+    assert_eq!(Loggy::installed().unwrap().prefix, "raw");
+}
@@ -0,0 +1,63 @@
+// FILE FLAKY TESTED
+
+#[macro_use]
+extern crate loggy;
+
+use loggy::{assert_logs_panics_snapshot, assert_logs_snapshot, assert_panics, Scope};
+
+#[test]
+fn log_should_match_snapshot() {
+    let result = assert_logs_snapshot("log_should_match_snapshot", || {
+        info!("information");
+        trace!("structured"; foo => 1, bar { baz => 2 });
+        1
+    });
+    assert_eq!(result, 1);
+}
+
+#[test]
+fn log_snapshot_may_contain_placeholders() {
+    assert_logs_snapshot("log_snapshot_may_contain_placeholders", || {
+        info!("took {} seconds", 1.5);
+    });
+}
+
+#[test]
+fn log_and_panic_should_match_snapshots() {
+    assert_logs_panics_snapshot("log_and_panic_should_match_snapshots", || {
+        Scope::with("scope", || {
+            warn!("warning");
+            error!("error");
+        });
+    });
+}
+
+/// Tests of failing snapshots would instead rewrite them when blessing.
+fn is_blessing() -> bool {
+    std::env::var("LOGGY_BLESS").is_ok_and(|var| !var.is_empty())
+}
+
+#[test]
+fn different_log_should_not_match_snapshot() {
+    if is_blessing() {
+        return;
+    }
+    assert_panics("actual log is different from expected log", || {
+        assert_logs_snapshot("log_should_match_snapshot", || {
+            info!("information");
+        });
+    });
+}
+
+#[test]
+fn missing_snapshot_should_fail() {
+    if is_blessing() {
+        return;
+    }
+    assert_panics(
+        "missing snapshot file: tests/snapshots/missing.log (set LOGGY_BLESS=1 to create it)",
+        || {
+            assert_logs_snapshot("missing", || {});
+        },
+    );
+}
@@ -0,0 +1,54 @@
+// FILE FLAKY TESTED
+
+#[macro_use]
+extern crate loggy;
+
+use loggy::{assert_logs_panics_snapshot, assert_logs_snapshot, Scope};
+use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+
+#[test]
+fn blessing_should_write_snapshots() {
+    let directory =
+        std::env::temp_dir().join(format!("loggy-test-snapshot-bless-{}", std::process::id()));
+    let snapshots = directory.join("tests").join("snapshots");
+    let _ = remove_dir_all(&directory);
+    create_dir_all(&snapshots).unwrap();
+    write(
+        snapshots.join("matching.log"),
+        "test: [INFO] test_snapshot_bless: took {number} seconds\n",
+    )
+    .unwrap();
+    write(
+        snapshots.join("different.log"),
+        "test: [INFO] test_snapshot_bless: old\n",
+    )
+    .unwrap();
+
+    std::env::set_var("CARGO_MANIFEST_DIR", &directory);
+    std::env::set_var("LOGGY_BLESS", "1");
+
+    assert_logs_snapshot("matching", || info!("took {} seconds", 1.5));
+    assert_logs_snapshot("different", || info!("new"));
+    assert_logs_panics_snapshot("nested/missing", || {
+        Scope::with("scope", || error!("error"));
+    });
+
+    assert_eq!(
+        read_to_string(snapshots.join("matching.log")).unwrap(),
+        "test: [INFO] test_snapshot_bless: took {number} seconds\n"
+    );
+    assert_eq!(
+        read_to_string(snapshots.join("different.log")).unwrap(),
+        "test: [INFO] test_snapshot_bless: new\n"
+    );
+    assert_eq!(
+        read_to_string(snapshots.join("nested").join("missing.log")).unwrap(),
+        "test: [ERROR] scope: error\n"
+    );
+    assert_eq!(
+        read_to_string(snapshots.join("nested").join("missing.panic")).unwrap(),
+        "test: [ERROR] scope: failed with 1 error(s)"
+    );
+
+    remove_dir_all(&directory).unwrap();
+}